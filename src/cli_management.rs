@@ -1,22 +1,36 @@
 use std::path::PathBuf;
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use log::debug;
+use targetgen_lib::generator::clutter::ClutterConfig;
+use targetgen_lib::generator::coco::merge::{self, ImageTransfer};
+use targetgen_lib::generator::config::ObjectSampling;
+use targetgen_lib::generator::manifest;
+use targetgen_lib::generator::split::SplitConfig;
+use targetgen_lib::generator::stats::DatasetStats;
 use targetgen_lib::generator::TargetGenerator;
+use targetgen_lib::objects::import::{self, BackgroundRemoval, ImportOptions};
+use targetgen_lib::objects::{validate, ObjectDetailsFile, ObjectManager, Padding, OBJECTS_FILE};
+use targetgen_lib::standard_targets::StandardTargetConfig;
 
+/// Without a subcommand the tool generates targets using the top level options
 #[derive(Parser, Debug)]
 #[clap(name = "targetgen", version = "0.1.0", author = "Declan Emery", about = "A tool for generating synthetic bird's eye view images for training machine learning models.")]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct TargetgenCli {
-	#[clap(short, long, help = "The path to the backgrounds image directory.")]
-	pub backgrounds: PathBuf,
+	#[clap(subcommand)]
+	pub command: Option<Command>,
 	
-	#[clap(short, long, help = "The path to the objects image directory.")]
-	pub objects: PathBuf,
+	#[clap(short, long, required = true, help = "The path to the backgrounds image directory.")]
+	pub backgrounds: Option<PathBuf>,
+	
+	#[clap(short, long, required = true, help = "The path to the objects image directory.")]
+	pub objects: Option<PathBuf>,
 
-	#[clap(short, long, help = "The output folder.")]
-	pub output: PathBuf,
+	#[clap(long, required = true, help = "The output folder.")]
+	pub output: Option<PathBuf>,
 	
-	#[clap(short, long, help = "The path to the annotations file.")]
-	pub annotations: PathBuf,
+	#[clap(short, long, required = true, help = "The path to the annotations file.")]
+	pub annotations: Option<PathBuf>,
 
 	#[clap(short, long, help = "Enable logging.")]
 	pub enable_logging: Option<bool>,
@@ -24,7 +38,7 @@ pub struct TargetgenCli {
 	#[clap(short, long, help = "The number of target images to generate.")]
 	pub num_targets: Option<u32>,
 
-	#[clap(short, long, help = "The number of objects per image.")]
+	#[clap(long, help = "The number of objects per image.")]
 	pub num_objects: Option<u32>,
 
 	#[clap(short, long, help = "Whether or not to visualize the bounding boxes of the objects.")]
@@ -36,7 +50,7 @@ pub struct TargetgenCli {
 	#[clap(short, long, help = "Whether or not to allow duplicates of the same object within the same generated target image.")]
 	pub permit_duplicates: Option<bool>,
 	
-	#[clap(short, long, help = "Whether or not to allow objects to collide with each other, AKA overlap.")]
+	#[clap(long, help = "Whether or not to allow objects to collide with each other, AKA overlap.")]
 	pub permit_collisions: Option<bool>,
 	
 	#[clap(short, long, help = "The size of the cache in MBs, which holds resized objects (initialization only).")]
@@ -45,23 +59,314 @@ pub struct TargetgenCli {
 	#[clap(short, long, help = "The number of worker threads to use for generating the target images.")]
 	pub worker_threads: Option<u8>,
 	
-	#[clap(short, long, help = "Whether or not to compress the generated target images.")]
+	#[clap(long, help = "Whether or not to compress the generated target images.")]
 	pub compress: Option<bool>,
 	
 	#[clap(short, long, help = "Should the objects be randomly rotated (currently only supports 90 degree rotations).")]
 	pub do_random_rotation: Option<bool>,
+	
+	#[clap(long, help = "The seed of the run, the same seed generates the same images regardless of the number of worker threads.")]
+	pub seed: Option<u64>,
+	
+	#[clap(long, value_enum, help = "How to choose the objects placed in each image, class weights are read from objects.json.")]
+	pub object_sampling: Option<SamplingArg>,
+	
+	#[clap(long, help = "Whether or not to write a JSON sidecar per image with every sampled generation parameter, off by default.")]
+	pub write_provenance: Option<bool>,
+	
+	#[clap(long, help = "Whether or not to also write KITTI labels, one .txt file per image.")]
+	pub export_kitti: Option<bool>,
+	
+	#[clap(long, help = "Whether or not to also write a CVAT for images XML file.")]
+	pub export_cvat: Option<bool>,
+	
+	#[clap(long, help = "Whether or not to also write LabelMe JSON files, one per image.")]
+	pub export_labelme: Option<bool>,
+	
+	#[clap(long, help = "Whether or not to also write YOLO labels, one .txt file per image.")]
+	pub export_yolo: Option<bool>,
+	
+	#[clap(long, help = "The fraction of images generated without any objects, as background-only negatives.")]
+	pub negative_ratio: Option<f32>,
+	
+	#[clap(long, help = "The largest sigma of the gaussian blur applied to each image.")]
+	pub max_blur_sigma: Option<f32>,
+	
+	#[clap(long, help = "The largest standard deviation of the gaussian noise added to each image.")]
+	pub max_noise_sigma: Option<f32>,
+	
+	#[clap(long, help = "The lowest camera angle above the horizon in degrees, used to pick the views of multi-view objects. 90 is straight down.")]
+	pub min_camera_elevation: Option<f32>,
+	
+	#[clap(long, help = "Draw runway markings, lines, skid marks and debris onto the backgrounds, with this many per 100 square meters.")]
+	pub clutter: Option<f32>,
+	
+	#[clap(long, help = "Ramp the difficulty up over the run, so that the first images are the easiest and the last the hardest.")]
+	pub curriculum: Option<bool>,
+	
+	#[clap(long, help = "Place procedural SUAS standard targets in each image, the fewest and most per image, ex. \"0,2\".")]
+	pub standard_targets: Option<String>,
+	
+	#[clap(long, help = "The smallest and largest width of the standard targets in meters, ex. \"0.3,1.2\".")]
+	pub standard_target_size: Option<String>,
+	
+	#[clap(long, help = "The path to a library of objects of non-interest that are placed beneath the objects, laid out like the objects directory.")]
+	pub distractors: Option<PathBuf>,
+	
+	#[clap(long, help = "The fewest and most distractors per image, ex. \"0,3\".")]
+	pub distractor_count: Option<String>,
+	
+	#[clap(long, help = "Whether or not to annotate the distractors with an ignore flag instead of leaving them out of the annotations.")]
+	pub annotate_distractors: Option<bool>,
+	
+	#[clap(long, help = "The file name of each image without the extension, must contain {index} or ex. {index:06}, may use {run_id}, {seed} and {background} placeholders.")]
+	pub name_template: Option<String>,
+	
+	#[clap(long, help = "The identifier of the run used for the {run_id} placeholder, defaults to the seed in hex.")]
+	pub run_id: Option<String>,
+	
+	#[clap(long, help = "Place the images in numbered subfolders that each hold this many images.")]
+	pub shard_size: Option<u32>,
+	
+	#[clap(long, help = "Divide the images into train/val/test splits with the given ratios, ex. \"0.8,0.1,0.1\".")]
+	pub splits: Option<String>,
+	
+	#[clap(long, help = "Assign whole backgrounds to a split so that no background is shared between splits.")]
+	pub split_by_background: Option<bool>,
+	
+	#[clap(long, help = "Continue an interrupted run in the same output folder, only generating the missing images. The other options must be the same as in the interrupted run, not supported with the CVAT export.")]
+	pub resume: Option<bool>,
 }
 
-pub fn run(args: TargetgenCli) {
-	if let Some(enable_logging) = args.enable_logging {
-		if enable_logging {
-			simple_logger::SimpleLogger::new().with_level(log::LevelFilter::Debug).init().unwrap();
+#[derive(Subcommand, Debug)]
+pub enum Command {
+	#[clap(about = "Merge several COCO datasets into one, renumbering ids and unifying categories by name.")]
+	Merge {
+		#[clap(short, long, required = true, num_args = 1.., help = "The annotation files to merge, images are expected relative to each file.")]
+		inputs: Vec<PathBuf>,
+		
+		#[clap(short, long, help = "The path of the merged annotations file.")]
+		output: PathBuf,
+		
+		#[clap(long, value_enum, default_value = "none", help = "What to do with the images of the merged datasets.")]
+		images: ImagesArg,
+	},
+	
+	#[clap(about = "Report statistics about a generated dataset as JSON and HTML.")]
+	Stats {
+		#[clap(short, long, help = "The annotations file of the dataset, images are expected relative to it.")]
+		annotations: PathBuf,
+		
+		#[clap(long, help = "Where to write the JSON report, defaults to stats.json next to the annotations file.")]
+		json: Option<PathBuf>,
+		
+		#[clap(long, help = "Where to write the HTML report, defaults to stats.html next to the annotations file.")]
+		html: Option<PathBuf>,
+	},
+	
+	#[clap(about = "Cut an object out of a photo on a plain or chroma-key background and add it to an object library.")]
+	Import {
+		#[clap(short, long, help = "The photo of the object.")]
+		input: PathBuf,
+		
+		#[clap(short, long, help = "The path to the objects image directory, holding objects.json.")]
+		objects: PathBuf,
+		
+		#[clap(long, help = "The object type (class) of the object.")]
+		class: u32,
+		
+		#[clap(long, help = "The name of the object type, required if it is not defined in objects.json yet.")]
+		class_name: Option<String>,
+		
+		#[clap(long, help = "The width of the object on the ground in meters.")]
+		ground_width: f32,
+		
+		#[clap(long, value_enum, default_value = "flood-fill", help = "How the background is told apart from the object.")]
+		method: RemovalArg,
+		
+		#[clap(long, help = "The color of the background as \"r,g,b\", estimated from the border of the photo if not set.")]
+		key_color: Option<String>,
+		
+		#[clap(long, help = "How far a color may be from the key color to count as background.")]
+		tolerance: Option<f32>,
+		
+		#[clap(long, help = "The file name of the sprite, defaults to the name of the photo.")]
+		name: Option<String>,
+	},
+	
+	#[clap(about = "Check an object library against its objects.json, exits with an error if anything is wrong. Also lists the transparent padding trimmed from each sprite.")]
+	Lint {
+		#[clap(short, long, help = "The path to the objects image directory.")]
+		objects: PathBuf,
+	},
+	
+	#[clap(about = "Check a dataset folder against its manifest.json, exits with an error if any file differs.")]
+	Verify {
+		#[clap(short, long, help = "The output folder of the dataset, which holds manifest.json.")]
+		dataset: PathBuf,
+		
+		#[clap(long, help = "Also check the backgrounds, sprites and objects.json the dataset was generated from.")]
+		check_inputs: bool,
+	},
+	
+	#[clap(about = "Write the objects.json that is equivalent to a library of <class_name>/*.png folders.")]
+	ExportObjects {
+		#[clap(short, long, help = "The path to the objects image directory.")]
+		objects: PathBuf,
+		
+		#[clap(long, help = "The folder to write objects.json to, defaults to the objects directory.")]
+		output: Option<PathBuf>,
+	},
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SamplingArg {
+	Sprite,
+	Class,
+	BalancedRun,
+}
+
+impl From<SamplingArg> for ObjectSampling {
+	fn from(value: SamplingArg) -> Self {
+		match value {
+			SamplingArg::Sprite => ObjectSampling::Sprite,
+			SamplingArg::Class => ObjectSampling::Class,
+			SamplingArg::BalancedRun => ObjectSampling::BalancedRun,
+		}
+	}
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum RemovalArg {
+	ChromaKey,
+	FloodFill,
+}
+
+impl From<RemovalArg> for BackgroundRemoval {
+	fn from(value: RemovalArg) -> Self {
+		match value {
+			RemovalArg::ChromaKey => BackgroundRemoval::ChromaKey,
+			RemovalArg::FloodFill => BackgroundRemoval::FloodFill,
+		}
+	}
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ImagesArg {
+	None,
+	Copy,
+	Symlink,
+}
+
+impl From<ImagesArg> for ImageTransfer {
+	fn from(value: ImagesArg) -> Self {
+		match value {
+			ImagesArg::None => ImageTransfer::None,
+			ImagesArg::Copy => ImageTransfer::Copy,
+			ImagesArg::Symlink => ImageTransfer::Symlink,
 		}
 	}
+}
+
+pub fn run(args: TargetgenCli) {
+	if let Some(true) = args.enable_logging {
+		simple_logger::SimpleLogger::new().with_level(log::LevelFilter::Debug).init().unwrap();
+	}
 	
 	debug!("Running with args: {:?}", args);
+	
+	match args.command {
+		Some(Command::Merge { inputs, output, images }) => {
+			let merged = merge::merge_files(&inputs, output, images.into()).unwrap();
+			println!("Merged {} datasets into {} images and {} annotations", inputs.len(), merged.images().len(), merged.annotations().len());
+		}
+		Some(Command::Stats { annotations, json, html }) => {
+			let stats = DatasetStats::load(&annotations).unwrap();
+			let json = json.unwrap_or(annotations.with_file_name("stats.json"));
+			let html = html.unwrap_or(annotations.with_file_name("stats.html"));
+			
+			stats.save(&json, &html).unwrap();
+			println!("Wrote statistics for {} images to {} and {}", stats.images, json.display(), html.display());
+		}
+		Some(Command::Import { input, objects, class, class_name, ground_width, method, key_color, tolerance, name }) => {
+			let mut options = ImportOptions {
+				object_type: class,
+				type_name: class_name,
+				ground_width,
+				removal: method.into(),
+				file_name: name,
+				..Default::default()
+			};
+			
+			if let Some(key_color) = key_color {
+				let channels = key_color.split(',').map(|c| c.trim().parse::<u8>().expect("Key color channels must be 0-255")).collect::<Vec<u8>>();
+				
+				if channels.len() != 3 {
+					panic!("Expected 3 key color channels (r,g,b), got {}", channels.len());
+				}
+				
+				options.key_color = Some([channels[0], channels[1], channels[2]]);
+			}
+			
+			if let Some(tolerance) = tolerance {
+				options.tolerance = tolerance;
+			}
+			
+			let path = import::import_object(&input, &objects, &options).unwrap();
+			println!("Added {} to {}", path.display(), objects.join(OBJECTS_FILE).display());
+		}
+		Some(Command::Lint { objects }) => {
+			let issues = validate::lint(&objects).unwrap();
+			
+			for issue in &issues {
+				println!("{}", issue);
+			}
+			
+			// not a problem, but the sprites could be cropped on disk to save the work on every load
+			let mut manager = ObjectManager::new(&objects);
+			if manager.load_objects().is_ok() {
+				for (file_name, padding) in manager.trimmed_padding().into_iter().filter(|(_, padding)| *padding != Padding::default()) {
+					println!("Trimmed {} of transparent padding from {}", padding, file_name);
+				}
+			}
+			
+			if !issues.is_empty() {
+				println!("Found {} problems in {}", issues.len(), objects.display());
+				std::process::exit(1);
+			}
+			
+			println!("No problems found in {}", objects.display());
+		}
+		Some(Command::Verify { dataset, check_inputs }) => {
+			let report = manifest::verify(&dataset, check_inputs).unwrap();
+			
+			for (problem, files) in [("Missing", &report.missing), ("Changed", &report.mismatched), ("Not in manifest", &report.unlisted)] {
+				for file in files {
+					println!("{}: {}", problem, file);
+				}
+			}
+			
+			if !report.is_ok() {
+				println!("Dataset does not match its manifest");
+				std::process::exit(1);
+			}
+			
+			println!("Verified {} files", report.verified);
+		}
+		Some(Command::ExportObjects { objects, output }) => {
+			let output = output.unwrap_or(objects.clone());
+			ObjectDetailsFile::load(&objects).unwrap().save(&output).unwrap();
+			
+			println!("Wrote {}", output.join(OBJECTS_FILE).display());
+		}
+		None => generate(args),
+	}
+	
+	debug!("Finished running.");
+}
 
-	let mut tg = TargetGenerator::new(args.backgrounds, args.objects, args.annotations).unwrap();
+fn generate(args: TargetgenCli) {
+	let mut tg = TargetGenerator::new(args.backgrounds.unwrap(), args.objects.unwrap(), args.annotations.unwrap()).unwrap();
 	
 	let num_targets = args.num_targets.unwrap_or(1);
 	let num_objects = args.num_objects.unwrap_or(6);
@@ -98,9 +403,124 @@ pub fn run(args: TargetgenCli) {
 		tg.config.do_random_rotation = do_random_rotation;
 	}
 	
-	tg.generate_targets(num_targets, ..num_objects, args.output).unwrap();
+	if let Some(seed) = args.seed {
+		tg.config.seed = Some(seed);
+	}
+	
+	if let Some(object_sampling) = args.object_sampling {
+		tg.config.object_sampling = object_sampling.into();
+	}
+	
+	if let Some(write_provenance) = args.write_provenance {
+		tg.config.write_provenance = write_provenance;
+	}
+	
+	if let Some(export_kitti) = args.export_kitti {
+		tg.config.export_kitti = export_kitti;
+	}
+	
+	if let Some(export_cvat) = args.export_cvat {
+		tg.config.export_cvat = export_cvat;
+	}
+	
+	if let Some(export_labelme) = args.export_labelme {
+		tg.config.export_labelme = export_labelme;
+	}
+	
+	if let Some(export_yolo) = args.export_yolo {
+		tg.config.export_yolo = export_yolo;
+	}
+	
+	if let Some(negative_ratio) = args.negative_ratio {
+		tg.config.negative_ratio = negative_ratio;
+	}
+	
+	if let Some(max_blur_sigma) = args.max_blur_sigma {
+		tg.config.max_blur_sigma = max_blur_sigma;
+	}
+	
+	if let Some(max_noise_sigma) = args.max_noise_sigma {
+		tg.config.max_noise_sigma = max_noise_sigma;
+	}
+	
+	if let Some(density) = args.clutter {
+		tg.config.clutter = Some(ClutterConfig { density });
+	}
+	
+	if let Some(min_camera_elevation) = args.min_camera_elevation {
+		tg.config.min_camera_elevation = min_camera_elevation;
+	}
+	
+	if let Some(curriculum) = args.curriculum {
+		tg.config.curriculum = curriculum;
+	}
+	
+	if let Some(standard_targets) = args.standard_targets {
+		let (min_count, max_count) = parse_pair::<u32>(&standard_targets, "standard target counts");
+		let mut config = StandardTargetConfig { min_count, max_count, ..Default::default() };
+		
+		if let Some(size) = args.standard_target_size {
+			(config.min_size, config.max_size) = parse_pair::<f32>(&size, "standard target sizes");
+		}
+		
+		tg.config.standard_targets = Some(config);
+	}
+	
+	if let Some(distractors) = args.distractors {
+		tg.load_distractors(distractors).unwrap();
+		let config = tg.config.distractors.as_mut().unwrap();
+		
+		if let Some(count) = args.distractor_count {
+			(config.min_count, config.max_count) = parse_pair::<u32>(&count, "distractor counts");
+		}
+		
+		if let Some(annotate_distractors) = args.annotate_distractors {
+			config.annotate = annotate_distractors;
+		}
+	}
+	
+	if let Some(name_template) = args.name_template {
+		tg.config.naming.template = name_template;
+	}
+	
+	if args.run_id.is_some() {
+		tg.config.naming.run_id = args.run_id;
+	}
+	
+	if args.shard_size.is_some() {
+		tg.config.naming.shard_size = args.shard_size;
+	}
+	
+	if let Some(splits) = args.splits {
+		let ratios = splits.split(',').map(|r| r.trim().parse::<f32>().expect("Split ratios must be numbers")).collect::<Vec<f32>>();
+		
+		if ratios.len() != 3 {
+			panic!("Expected 3 split ratios (train,val,test), got {}", ratios.len());
+		}
+		
+		tg.config.splits = Some(SplitConfig {
+			train: ratios[0],
+			val: ratios[1],
+			test: ratios[2],
+			by_background: args.split_by_background.unwrap_or(false),
+		});
+	}
+	
+	if let Some(resume) = args.resume {
+		tg.config.resume = resume;
+	}
+	
+	tg.generate_targets(num_targets, ..num_objects, args.output.unwrap()).unwrap();
 	
 	tg.close();
+}
+
+/// Parse a "min,max" pair of numbers
+fn parse_pair<T: std::str::FromStr>(value: &str, name: &str) -> (T, T) {
+	let values = value.split(',').map(|v| v.trim().parse::<T>().unwrap_or_else(|_| panic!("The {} must be numbers", name))).collect::<Vec<T>>();
 	
-	debug!("Finished running.");
-}
\ No newline at end of file
+	match <[T; 2]>::try_from(values) {
+		Ok([min, max]) => (min, max),
+		Err(values) => panic!("Expected 2 {} (min,max), got {}", name, values.len()),
+	}
+}
@@ -1,11 +1,15 @@
 mod cli_management;
 
 use clap::Parser;
+use crate::cli_management::run;
+#[cfg(test)]
 use log::LevelFilter;
+#[cfg(test)]
 use simple_logger::SimpleLogger;
+#[cfg(test)]
 use targetgen_lib::generator;
+#[cfg(test)]
 use targetgen_lib::generator::TargetGenerator;
-use crate::cli_management::run;
 
 fn main() {
 	run(cli_management::TargetgenCli::parse());
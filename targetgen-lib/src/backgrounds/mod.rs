@@ -7,7 +7,7 @@ use chrono::{DateTime, Local};
 use image::{RgbaImage};
 use log::{debug, warn};
 use rand::seq::SliceRandom;
-use rand::{thread_rng};
+use rand::Rng;
 use rayon::iter::ParallelBridge;
 use crate::generator::error::GenerationError;
 
@@ -56,21 +56,22 @@ impl BackgroundLoader {
 			debug!("Loaded image in {}ms: {}", start.elapsed().as_millis(), path_name.to_string().replace("\\", "/"));
 		});
 		
-		let x = Ok(Self { // don't ask why we need to make a variable here, it just works
+		// sort so that the same random choices pick the same backgrounds regardless of load order
+		{
+			let mut backgrounds = v.lock().unwrap();
+			backgrounds.sort_by(|a, b| a.filename.cmp(&b.filename));
+			backgrounds.iter_mut().enumerate().for_each(|(i, bg)| bg.id = i as u32);
+		}
+		
+		Ok(Self {
 			backgrounds: v
-		});
-
-		x
+		})
 	}
 
-	pub fn random(&self) -> Option<BackgroundImage> {
+	pub fn random<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<BackgroundImage> {
 		let lock = self.backgrounds.lock().unwrap();
 		
-		if let Some(bg) = lock.choose(&mut thread_rng()) {
-			Some(bg.clone())
-		} else {
-			None
-		}
+		lock.choose(rng).cloned()
 	}
 }
 
@@ -0,0 +1,243 @@
+use ab_glyph::{FontRef, PxScale};
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+use imageproc::point::Point;
+use rand::Rng;
+use serde::Serialize;
+use crate::generator::placement::AugmentationRecord;
+use crate::standard_targets::FONT;
+
+/// How much procedural ground clutter is drawn onto each background before the objects are placed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ClutterConfig {
+	/// the average number of markings, lines and skid marks per 100 square meters of ground, debris is
+	/// three times as common
+	pub density: f32,
+}
+
+impl Default for ClutterConfig {
+	fn default() -> Self {
+		Self {
+			density: 0.5,
+		}
+	}
+}
+
+/// The painted white of runway and road markings
+const MARKING_WHITE: Rgba<u8> = Rgba([235, 235, 230, 255]);
+/// The painted yellow of taxi lines
+const TAXI_YELLOW: Rgba<u8> = Rgba([225, 185, 40, 255]);
+const SKID_BLACK: Rgba<u8> = Rgba([25, 25, 25, 255]);
+
+/// Draw markings, lines, tire skid marks and debris onto [image] at their real size, given how many
+/// pixels one meter of ground covers. Returns what was drawn for the provenance of the image.
+pub fn draw_clutter<R: Rng + ?Sized>(image: &mut RgbaImage, config: &ClutterConfig, pixels_per_meter: f32, rng: &mut R) -> AugmentationRecord {
+	let area = image.width() as f32 * image.height() as f32 / (pixels_per_meter * pixels_per_meter);
+	let expected = config.density.max(0.0) * area / 100.0;
+	let mut count = |expected: f32| expected as u32 + rng.gen_bool(expected.fract() as f64) as u32;
+	let (features, debris) = (count(expected), count(expected * 3.0));
+	
+	let mut record = AugmentationRecord::new("clutter");
+	let mut drawn = [0u32; 5];
+	
+	for _ in 0..features {
+		let kind = rng.gen_range(0..4);
+		match kind {
+			0 => draw_runway_number(image, pixels_per_meter, rng),
+			1 => draw_line(image, pixels_per_meter, rng),
+			2 => draw_dashes(image, pixels_per_meter, rng),
+			_ => draw_skid_marks(image, pixels_per_meter, rng),
+		}
+		drawn[kind] += 1;
+	}
+	
+	for _ in 0..debris {
+		draw_debris(image, pixels_per_meter, rng);
+	}
+	drawn[4] = debris;
+	
+	for (name, count) in ["runway_numbers", "lines", "dashes", "skid_marks", "debris"].iter().zip(drawn) {
+		record = record.with(name, count);
+	}
+	
+	record
+}
+
+/// A random point on the image along with a random direction in radians
+fn random_pose<R: Rng + ?Sized>(image: &RgbaImage, rng: &mut R) -> ((f32, f32), f32) {
+	let point = (rng.gen_range(0.0..image.width() as f32), rng.gen_range(0.0..image.height() as f32));
+	
+	(point, rng.gen_range(0.0..std::f32::consts::TAU))
+}
+
+/// A runway designator, ex. "27", painted in white a few meters tall and facing any direction
+fn draw_runway_number<R: Rng + ?Sized>(image: &mut RgbaImage, pixels_per_meter: f32, rng: &mut R) {
+	let font = FontRef::try_from_slice(FONT).expect("The bundled font is valid");
+	let text = format!("{:02}", rng.gen_range(1..=36));
+	let scale = PxScale::from(rng.gen_range(3.0..9.0) * pixels_per_meter);
+	let ((x, y), angle) = random_pose(image, rng);
+	let opacity = rng.gen_range(0.6..0.95);
+	
+	// draw onto a square that fits the text at any angle, then turn it
+	let (w, h) = imageproc::drawing::text_size(scale, &font, &text);
+	let side = ((w * w + h * h) as f32).sqrt().ceil() as u32;
+	let mut mask = GrayImage::new(side, side);
+	imageproc::drawing::draw_text_mut(&mut mask, Luma([255]), ((side - w) / 2) as i32, ((side - h) / 2) as i32, scale, &font, &text);
+	let mask = rotate_about_center(&mask, angle, Interpolation::Bilinear, Luma([0]));
+	
+	let (left, top) = (x as i64 - side as i64 / 2, y as i64 - side as i64 / 2);
+	for (mx, my, coverage) in mask.enumerate_pixels() {
+		let (px, py) = (left + mx as i64, top + my as i64);
+		if (0..image.width() as i64).contains(&px) && (0..image.height() as i64).contains(&py) {
+			blend(image.get_pixel_mut(px as u32, py as u32), MARKING_WHITE, opacity * coverage[0] as f32 / 255.0);
+		}
+	}
+}
+
+/// A long taxi line or road edge line
+fn draw_line<R: Rng + ?Sized>(image: &mut RgbaImage, pixels_per_meter: f32, rng: &mut R) {
+	let (start, angle) = random_pose(image, rng);
+	let length = rng.gen_range(10.0..60.0) * pixels_per_meter;
+	let width = rng.gen_range(0.1..0.3) * pixels_per_meter;
+	let color = if rng.gen_bool(0.5) { TAXI_YELLOW } else { MARKING_WHITE };
+	let end = (start.0 + length * angle.cos(), start.1 + length * angle.sin());
+	let opacity = rng.gen_range(0.6..0.95);
+	
+	fill_polygon(image, &thick_segment(start, end, width), color, opacity);
+}
+
+/// A dashed center line of painted stripes
+fn draw_dashes<R: Rng + ?Sized>(image: &mut RgbaImage, pixels_per_meter: f32, rng: &mut R) {
+	let (start, angle) = random_pose(image, rng);
+	let dash = rng.gen_range(3.0..6.0) * pixels_per_meter;
+	let gap = rng.gen_range(3.0..9.0) * pixels_per_meter;
+	let width = rng.gen_range(0.3..0.9) * pixels_per_meter;
+	let opacity = rng.gen_range(0.6..0.95);
+	let (dx, dy) = (angle.cos(), angle.sin());
+	
+	for i in 0..rng.gen_range(2..6) {
+		let offset = i as f32 * (dash + gap);
+		let from = (start.0 + offset * dx, start.1 + offset * dy);
+		let to = (from.0 + dash * dx, from.1 + dash * dy);
+		fill_polygon(image, &thick_segment(from, to, width), MARKING_WHITE, opacity);
+	}
+}
+
+/// A pair of faint, slightly curving tire tracks
+fn draw_skid_marks<R: Rng + ?Sized>(image: &mut RgbaImage, pixels_per_meter: f32, rng: &mut R) {
+	let (start, mut angle) = random_pose(image, rng);
+	let length = rng.gen_range(5.0..20.0) * pixels_per_meter;
+	let width = rng.gen_range(0.15..0.3) * pixels_per_meter;
+	let track = rng.gen_range(1.4..1.9) * pixels_per_meter;
+	let curve = rng.gen_range(-0.02..0.02);
+	let opacity = rng.gen_range(0.2..0.5);
+	
+	let segments = 10;
+	let step = length / segments as f32;
+	let mut center = start;
+	for _ in 0..segments {
+		let next = (center.0 + step * angle.cos(), center.1 + step * angle.sin());
+		let (nx, ny) = (-angle.sin() * track / 2.0, angle.cos() * track / 2.0);
+		
+		for side in [-1.0, 1.0] {
+			let from = (center.0 + side * nx, center.1 + side * ny);
+			let to = (next.0 + side * nx, next.1 + side * ny);
+			fill_polygon(image, &thick_segment(from, to, width), SKID_BLACK, opacity);
+		}
+		
+		center = next;
+		angle += curve * step / pixels_per_meter;
+	}
+}
+
+/// A small irregular piece of gravel, rubber or litter
+fn draw_debris<R: Rng + ?Sized>(image: &mut RgbaImage, pixels_per_meter: f32, rng: &mut R) {
+	let ((x, y), rotation) = random_pose(image, rng);
+	let radius = rng.gen_range(0.03..0.15) * pixels_per_meter;
+	let shade = rng.gen_range(40..200);
+	let color = Rgba([shade, (shade as f32 * 0.95) as u8, (shade as f32 * 0.85) as u8, 255]);
+	
+	let corners = rng.gen_range(4..8);
+	let points = (0..corners).map(|i| {
+		let angle = rotation + i as f32 * std::f32::consts::TAU / corners as f32;
+		let r = radius * rng.gen_range(0.5..1.0);
+		(x + r * angle.cos(), y + r * angle.sin())
+	}).collect::<Vec<_>>();
+	
+	fill_polygon(image, &points, color, 1.0);
+}
+
+/// The corners of a line from [from] to [to] that is [width] pixels thick
+fn thick_segment(from: (f32, f32), to: (f32, f32), width: f32) -> [(f32, f32); 4] {
+	let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+	let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+	let (nx, ny) = (-dy / length * width / 2.0, dx / length * width / 2.0);
+	
+	[(from.0 + nx, from.1 + ny), (to.0 + nx, to.1 + ny), (to.0 - nx, to.1 - ny), (from.0 - nx, from.1 - ny)]
+}
+
+/// Paint a polygon onto [image], worn paint with a low [opacity] lets the ground show through
+fn fill_polygon(image: &mut RgbaImage, points: &[(f32, f32)], color: Rgba<u8>, opacity: f32) {
+	let mut points = points.iter().map(|(x, y)| Point::new(x.round() as i32, y.round() as i32)).collect::<Vec<_>>();
+	points.dedup();
+	if points.len() > 1 && points.first() == points.last() {
+		points.pop();
+	}
+	if points.len() < 3 {
+		return; // too small to cover a pixel
+	}
+	
+	// only rasterize the part of the image that the polygon covers
+	let left = points.iter().map(|p| p.x).min().unwrap().max(0);
+	let top = points.iter().map(|p| p.y).min().unwrap().max(0);
+	let right = points.iter().map(|p| p.x).max().unwrap().min(image.width() as i32 - 1);
+	let bottom = points.iter().map(|p| p.y).max().unwrap().min(image.height() as i32 - 1);
+	if left > right || top > bottom {
+		return; // outside of the image
+	}
+	
+	let mut mask = GrayImage::new((right - left + 1) as u32, (bottom - top + 1) as u32);
+	let local = points.iter().map(|p| Point::new(p.x - left, p.y - top)).collect::<Vec<_>>();
+	imageproc::drawing::draw_polygon_mut(&mut mask, &local, Luma([255]));
+	
+	for (x, y, coverage) in mask.enumerate_pixels() {
+		blend(image.get_pixel_mut(x + left as u32, y + top as u32), color, opacity * coverage[0] as f32 / 255.0);
+	}
+}
+
+fn blend(pixel: &mut Rgba<u8>, color: Rgba<u8>, alpha: f32) {
+	if alpha <= 0.0 {
+		return;
+	}
+	
+	for c in 0..3 {
+		pixel[c] = (pixel[c] as f32 * (1.0 - alpha) + color[c] as f32 * alpha).round() as u8;
+	}
+}
+
+#[test]
+fn test_draw_clutter() {
+	use rand::rngs::StdRng;
+	use rand::SeedableRng;
+	
+	let ground = RgbaImage::from_pixel(400, 300, Rgba([100, 100, 100, 255]));
+	let clutter = |density: f32, seed: u64| {
+		let mut image = ground.clone();
+		let record = draw_clutter(&mut image, &ClutterConfig { density }, 20.0, &mut StdRng::seed_from_u64(seed));
+		(image, record)
+	};
+	
+	// 300 square meters at a density of 2 is 6 features and 18 pieces of debris
+	let (image, record) = clutter(2.0, 1);
+	assert_eq!(record.parameters["debris"], 18);
+	assert_eq!(["runway_numbers", "lines", "dashes", "skid_marks"].iter().map(|k| record.parameters[*k].as_u64().unwrap()).sum::<u64>(), 6);
+	assert_ne!(image, ground);
+	assert_eq!(clutter(2.0, 1).0, image);
+	assert_eq!(clutter(0.0, 1).0, ground);
+	
+	// a 0.5 meter wide line at 20 pixels per meter is 10 pixels wide
+	let mut line = ground.clone();
+	fill_polygon(&mut line, &thick_segment((50.0, 100.0), (350.0, 100.0), 0.5 * 20.0), MARKING_WHITE, 1.0);
+	let painted = (0..300).filter(|y| line.get_pixel(200, *y) == &MARKING_WHITE).count();
+	assert!((10..=11).contains(&painted), "{} pixels", painted);
+}
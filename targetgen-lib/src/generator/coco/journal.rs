@@ -0,0 +1,141 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::generator::coco::{CocoAnnotation, CocoCategory, CocoFormatFile, CocoImage};
+use crate::generator::config::ObjectSampling;
+use crate::generator::error::GenerationError;
+
+/// A single line of the annotation journal
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "entry", rename_all = "snake_case")]
+pub enum JournalEntry {
+	/// Written once when the journal is created, along with the seed and parameters of the run so that
+	/// a resumed run plans the same images
+	Header {
+		categories: Vec<CocoCategory>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		seed: Option<u64>,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		run: Option<RunParameters>,
+	},
+	/// Written as soon as an image and all of its annotations are complete
+	Image { image: CocoImage, annotations: Vec<CocoAnnotation> },
+}
+
+/// The parameters of a run that the plan of each image depends on besides the seed, ex. which images
+/// are negatives. A run can only be resumed with the same parameters.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RunParameters {
+	/// the number of images in the run
+	pub amount: u32,
+	/// the exclusive upper bound of the number of objects in an image
+	pub max_objects: u32,
+	pub negative_ratio: f32,
+	pub object_sampling: ObjectSampling,
+	pub curriculum: bool,
+}
+
+/// An append-only JSON Lines file that holds every completed image and its annotations. Unlike the
+/// annotations.json file, which is only written at the very end of a run, the journal is written to
+/// as the run progresses so that the annotations survive a crash.
+#[derive(Debug)]
+pub struct AnnotationJournal {
+	path: PathBuf,
+	file: Option<File>,
+}
+
+impl AnnotationJournal {
+	pub fn new<P: AsRef<Path>>(path: P) -> Self {
+		Self {
+			path: path.as_ref().to_path_buf(),
+			file: None,
+		}
+	}
+	
+	/// The path of the journal that belongs to an annotations file, ex. annotations.json -> annotations.jsonl
+	pub fn journal_path<P: AsRef<Path>>(annotations_path: P) -> PathBuf {
+		annotations_path.as_ref().with_extension("jsonl")
+	}
+	
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+	
+	/// Whether or not anything has been written to the journal by this run yet
+	pub fn is_open(&self) -> bool {
+		self.file.is_some()
+	}
+	
+	/// Append a single entry to the journal. The first append of a run replaces any previous journal.
+	pub fn append(&mut self, entry: &JournalEntry) -> Result<(), GenerationError> {
+		if self.file.is_none() {
+			self.file = Some(OpenOptions::new().write(true).truncate(true).create(true).open(&self.path)?);
+		}
+		
+		let mut line = serde_json::to_string(entry)?;
+		line.push('\n');
+		
+		// write the whole line at once so that a crash can at worst leave the final line incomplete
+		let file = self.file.as_mut().unwrap();
+		file.write_all(line.as_bytes())?;
+		file.flush()?;
+		
+		Ok(())
+	}
+	
+	/// Read every entry back from a journal on disk. An incomplete final line, which is what a crash
+	/// in the middle of a write leaves behind, is skipped.
+	pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<JournalEntry>, GenerationError> {
+		let lines = BufReader::new(File::open(path.as_ref())?).lines().collect::<Result<Vec<String>, _>>()?;
+		let mut entries = Vec::with_capacity(lines.len());
+		
+		for (i, line) in lines.iter().enumerate() {
+			if line.trim().is_empty() {
+				continue;
+			}
+			
+			match serde_json::from_str(line) {
+				Ok(entry) => entries.push(entry),
+				Err(e) if i == lines.len() - 1 => {
+					warn!("Skipping incomplete final line of journal {}: {}", path.as_ref().display(), e);
+				}
+				Err(e) => return Err(e.into()),
+			}
+		}
+		
+		Ok(entries)
+	}
+	
+	/// The seed and parameters of the run recorded in the header of a journal on disk, if it has them
+	pub fn run<P: AsRef<Path>>(path: P) -> Result<(Option<u64>, Option<RunParameters>), GenerationError> {
+		Ok(Self::read(path)?.into_iter().find_map(|entry| match entry {
+			JournalEntry::Header { seed, run, .. } => Some((seed, run)),
+			JournalEntry::Image { .. } => None,
+		}).unwrap_or_default())
+	}
+	
+	/// Assemble a complete COCO file from a journal on disk
+	pub fn assemble<P: AsRef<Path>>(path: P) -> Result<CocoFormatFile, GenerationError> {
+		let mut file = CocoFormatFile::default();
+		
+		for entry in Self::read(path)? {
+			match entry {
+				JournalEntry::Header { categories, .. } => file.categories = categories,
+				JournalEntry::Image { image, annotations } => {
+					file.images.push(image);
+					file.annotations.extend(annotations);
+				}
+			}
+		}
+		
+		// images finish in whatever order the worker threads get to them, so number the annotations in
+		// image order to make the file the same no matter how many threads were used
+		file.images.sort_by_key(|image| image.id);
+		file.annotations.sort_by_key(|annotation| (annotation.image_id, annotation.id));
+		file.annotations.iter_mut().enumerate().for_each(|(i, annotation)| annotation.id = Some(i as u32));
+		
+		Ok(file)
+	}
+}
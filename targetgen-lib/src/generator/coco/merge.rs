@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use crate::generator::coco::{CocoCategory, CocoFormatFile};
+use crate::generator::error::GenerationError;
+
+/// What to do with the images of the datasets that are merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageTransfer {
+	/// leave the images where they are, only the annotations are merged
+	None,
+	Copy,
+	Symlink,
+}
+
+/// Merge several COCO files into one. Image and annotation ids are renumbered in the order the files
+/// are given, and categories with the same name are unified into one category. A category keeps its
+/// id from the first file it appears in unless that id is already taken by another name.
+/// Fails if two images share a file name.
+pub fn merge(files: &[CocoFormatFile]) -> Result<CocoFormatFile, GenerationError> {
+	let mut merged = CocoFormatFile::default();
+	let mut category_ids: HashMap<String, u32> = HashMap::new();
+	let mut file_names = HashSet::new();
+	
+	for file in files {
+		// map the category ids of this file onto the merged categories
+		let mut category_map = HashMap::new();
+		for category in &file.categories {
+			let id = if let Some(id) = category_ids.get(&category.name) {
+				*id
+			} else {
+				let taken = category_ids.values().any(|id| *id == category.id);
+				let id = if taken { category_ids.values().max().unwrap() + 1 } else { category.id };
+				
+				category_ids.insert(category.name.clone(), id);
+				merged.categories.push(CocoCategory {
+					id,
+					name: category.name.clone(),
+					supercategory: category.supercategory.clone(),
+				});
+				id
+			};
+			
+			category_map.insert(category.id, id);
+		}
+		
+		let mut image_map = HashMap::new();
+		for image in &file.images {
+			if !file_names.insert(image.file_name.clone()) {
+				return Err(GenerationError::FileNameCollision(image.file_name.clone()));
+			}
+			
+			let mut image = image.clone();
+			image_map.insert(image.id, merged.images.len() as u32);
+			image.id = merged.images.len() as u32;
+			merged.images.push(image);
+		}
+		
+		for annotation in &file.annotations {
+			let mut annotation = annotation.clone();
+			annotation.id = Some(merged.annotations.len() as u32);
+			annotation.image_id = *image_map.get(&annotation.image_id)
+				.ok_or(GenerationError::GenericError(format!("Annotation refers to missing image {}", annotation.image_id)))?;
+			annotation.category_id = *category_map.get(&annotation.category_id)
+				.ok_or(GenerationError::GenericError(format!("Annotation refers to missing category {}", annotation.category_id)))?;
+			merged.annotations.push(annotation);
+		}
+		
+		for license in &file.licenses {
+			if !merged.licenses.contains(license) {
+				merged.licenses.push(license.clone());
+			}
+		}
+	}
+	
+	merged.categories.sort_by_key(|category| category.id);
+	
+	Ok(merged)
+}
+
+/// Merge several annotation files on disk into [output]. The images of each file are expected to be
+/// relative to the folder the annotation file is in, and are copied or linked into the folder of
+/// [output] depending on [transfer].
+pub fn merge_files<P: AsRef<Path>>(annotation_paths: &[P], output: P, transfer: ImageTransfer) -> Result<CocoFormatFile, GenerationError> {
+	let files = annotation_paths.iter().map(CocoFormatFile::load).collect::<Result<Vec<CocoFormatFile>, GenerationError>>()?;
+	let merged = merge(&files)?;
+	let output_folder = parent_folder(output.as_ref());
+	
+	if transfer != ImageTransfer::None {
+		for (path, file) in annotation_paths.iter().zip(&files) {
+			let source_folder = parent_folder(path.as_ref());
+			
+			for image in &file.images {
+				let (source, destination) = (source_folder.join(&image.file_name), output_folder.join(&image.file_name));
+				
+				if let Some(parent) = destination.parent() {
+					std::fs::create_dir_all(parent)?;
+				}
+				
+				match transfer {
+					ImageTransfer::Copy => {
+						std::fs::copy(&source, &destination)?;
+					}
+					ImageTransfer::Symlink => symlink(&std::fs::canonicalize(&source)?, &destination)?,
+					ImageTransfer::None => {}
+				}
+			}
+		}
+	}
+	
+	merged.save(output)?;
+	
+	Ok(merged)
+}
+
+fn parent_folder(path: &Path) -> PathBuf {
+	path.parent().map(|p| p.to_path_buf()).unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn symlink(source: &Path, destination: &Path) -> std::io::Result<()> {
+	std::os::unix::fs::symlink(source, destination)
+}
+
+#[cfg(windows)]
+fn symlink(source: &Path, destination: &Path) -> std::io::Result<()> {
+	std::os::windows::fs::symlink_file(source, destination)
+}
+
+#[test]
+fn test_merge() {
+	use crate::generator::coco::{BoundingBox, CocoGenerator};
+	
+	let bbox = BoundingBox { x: 0, y: 0, width: 1, height: 1 };
+	
+	let mut a = CocoGenerator::new("a.json", vec![CocoCategory::new(0, "car".to_string()), CocoCategory::new(1, "tire".to_string())]);
+	a.add_image(0, 10, 10, "a_0.png".to_string(), "grass.png".to_string(), None);
+	a.add_annotation(0, 1, 0, vec![], 1.0, bbox);
+	a.add_image(1, 10, 10, "a_1.png".to_string(), "grass.png".to_string(), None);
+	a.add_annotation(1, 0, 0, vec![], 1.0, bbox);
+	
+	let mut b = CocoGenerator::new("b.json", vec![CocoCategory::new(0, "tire".to_string()), CocoCategory::new(5, "boat".to_string())]);
+	b.add_image(0, 10, 10, "b_0.png".to_string(), "grass.png".to_string(), None);
+	b.add_annotation(0, 0, 0, vec![], 1.0, bbox);
+	b.add_annotation(0, 5, 0, vec![], 1.0, bbox);
+	
+	let merged = merge(&[a.file.clone(), b.file.clone()]).unwrap();
+	let names = merged.categories().iter().map(|c| (c.id(), c.name())).collect::<Vec<(u32, &str)>>();
+	assert_eq!(names, vec![(0, "car"), (1, "tire"), (5, "boat")]);
+	
+	assert_eq!(merged.images().iter().map(|i| i.id()).collect::<Vec<u32>>(), vec![0, 1, 2]);
+	assert_eq!(merged.annotations().iter().map(|a| (a.id().unwrap(), a.image_id(), a.category_id())).collect::<Vec<_>>(),
+		vec![(0, 0, 1), (1, 1, 0), (2, 2, 1), (3, 2, 5)]);
+	
+	assert!(matches!(merge(&[a.file.clone(), a.file.clone()]), Err(GenerationError::FileNameCollision(_))));
+}
@@ -1,10 +1,18 @@
 // https://docs.aws.amazon.com/rekognition/latest/customlabels-dg/md-coco-overview.html
 
-use std::fs::OpenOptions;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
 use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 use chrono::{DateTime, Datelike, Local};
 use serde::{Deserialize, Serialize};
+use crate::generator::coco::journal::{AnnotationJournal, JournalEntry, RunParameters};
+use crate::generator::difficulty::Difficulty;
+use crate::generator::error::GenerationError;
+use crate::generator::placement::{Attributes, Placement};
+
+pub mod journal;
+pub mod merge;
 
 /// Bounding box format: [x, y, width, height] where 0,0 is the top left corner
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,)]
@@ -22,21 +30,31 @@ impl BoundingBox {
 }
 
 pub struct CocoGenerator {
-	image_id: u32,
 	annotation_id: u32,
 	category_id: u32,
 	pub file: CocoFormatFile,
 	file_path: PathBuf,
+	journal: AnnotationJournal,
+	/// the seed of the run, written to the journal header
+	seed: Option<u64>,
+	/// the parameters of the run, written to the journal header
+	run: Option<RunParameters>,
+	/// the index of each image that has not been journaled yet in [file], along with the index its
+	/// annotations start at, so that journaling an image does not search every annotation of the run
+	unjournaled: HashMap<u32, (usize, usize)>,
 }
 
 impl CocoGenerator {
 	pub fn new<Q: AsRef<Path>>(file_path: Q, categories: Vec<CocoCategory>) -> Self {
 		let mut s = Self {
-			image_id: 0,
 			annotation_id: 0,
 			category_id: 0,
 			file: CocoFormatFile::default(),
 			file_path: file_path.as_ref().to_path_buf(),
+			journal: AnnotationJournal::new(AnnotationJournal::journal_path(&file_path)),
+			seed: None,
+			run: None,
+			unjournaled: HashMap::new(),
 		};
 		
 		s.file.categories = categories;
@@ -44,6 +62,38 @@ impl CocoGenerator {
 		s
 	}
 	
+	/// Add a category that is not known up front, unless a category with its id already exists
+	pub fn add_category(&mut self, category: CocoCategory) {
+		if !self.file.categories.iter().any(|c| c.id == category.id) {
+			self.file.categories.push(category);
+		}
+	}
+	
+	/// The path of the annotations.json file
+	pub fn file_path(&self) -> &Path {
+		&self.file_path
+	}
+	
+	/// The seed of the run, once it has been set
+	pub fn seed(&self) -> Option<u64> {
+		self.seed
+	}
+	
+	/// Set the seed and parameters of the run, before the first image is journaled
+	pub fn set_run(&mut self, seed: u64, run: RunParameters) {
+		self.seed = Some(seed);
+		self.run = Some(run);
+	}
+	
+	/// The seed and parameters recorded in the journal of an interrupted run, if there is a journal
+	pub fn journal_run(&self) -> Result<(Option<u64>, Option<RunParameters>), GenerationError> {
+		if self.journal.path().is_file() {
+			AnnotationJournal::run(self.journal.path())
+		} else {
+			Ok((None, None))
+		}
+	}
+	
 	/// Write the contents of the annotations.json file to disk
 	pub fn save(&self) {
 		let file = OpenOptions::new().write(true).truncate(true).create(true).open(&self.file_path).unwrap();
@@ -51,36 +101,139 @@ impl CocoGenerator {
 		serde_json::to_writer_pretty(file, &self.file).unwrap();
 	}
 	
-	/// Add a background image in, then return the image id
-	pub fn add_image(&mut self, width: u32, height: u32, file_name: String, date_captured: String) -> u32 {
+	/// Append an image that was previously added, along with all of its annotations, to the journal.
+	/// Should be called once the image and all of its annotations have been added, and only once per image.
+	pub fn journal_image(&mut self, image_id: u32) -> Result<(), GenerationError> {
+		if !self.journal.is_open() {
+			self.journal.append(&JournalEntry::Header { categories: self.file.categories.clone(), seed: self.seed, run: self.run })?;
+		}
+		
+		let (image_index, first_annotation) = self.unjournaled.remove(&image_id)
+			.ok_or(GenerationError::GenericError(format!("Image {} has not been added or is already journaled", image_id)))?;
+		let image = self.file.images[image_index].clone();
+		let annotations = self.file.annotations[first_annotation..].iter().filter(|annotation| annotation.image_id == image_id).cloned().collect();
+		
+		self.journal.append(&JournalEntry::Image { image, annotations })
+	}
+	
+	/// Pick up where an interrupted run left off. Every image in the journal whose file still exists in
+	/// [output] is reloaded along with its annotations, and the journal is rewritten to hold only those
+	/// images. The reloaded annotations are renumbered and new annotations continue after them.
+	/// Returns the ids of the reloaded images.
+	pub fn resume<P: AsRef<Path>>(&mut self, output: P) -> Result<HashSet<u32>, GenerationError> {
+		let journal_path = self.journal.path().to_path_buf();
+		
+		if !journal_path.is_file() {
+			return Ok(HashSet::new());
+		}
+		
+		// the latest entry wins if an image was generated more than once
+		let mut completed = BTreeMap::new();
+		for entry in AnnotationJournal::read(&journal_path)? {
+			if let JournalEntry::Image { image, annotations } = entry && output.as_ref().join(&image.file_name).is_file() {
+				completed.insert(image.id, (image, annotations));
+			}
+		}
+		
+		self.file.images.clear();
+		self.file.annotations.clear();
+		self.unjournaled.clear();
+		self.journal = AnnotationJournal::new(&journal_path);
+		self.journal.append(&JournalEntry::Header { categories: self.file.categories.clone(), seed: self.seed, run: self.run })?;
+		
+		// renumber the annotations so that the ids of dropped images do not leave gaps
+		self.annotation_id = 0;
+		for (image, mut annotations) in completed.into_values() {
+			for annotation in annotations.iter_mut() {
+				annotation.id = Some(self.annotation_id);
+				self.annotation_id += 1;
+			}
+			
+			self.journal.append(&JournalEntry::Image { image: image.clone(), annotations: annotations.clone() })?;
+			self.file.images.push(image);
+			self.file.annotations.extend(annotations);
+		}
+		
+		Ok(self.file.images.iter().map(|image| image.id).collect())
+	}
+	
+	/// Assemble the annotations.json file from the journal and write it to disk. If nothing was
+	/// journaled the in-memory annotations are written as is.
+	pub fn finalize(&mut self) -> Result<(), GenerationError> {
+		if self.journal.is_open() {
+			self.file = AnnotationJournal::assemble(self.journal.path())?;
+		}
+		
+		self.save();
+		
+		Ok(())
+	}
+	
+	/// Rebuild an annotations.json file from the journal left behind by a run that did not finish
+	pub fn recover<P: AsRef<Path>>(file_path: P) -> Result<CocoFormatFile, GenerationError> {
+		let file = AnnotationJournal::assemble(AnnotationJournal::journal_path(&file_path))?;
+		file.save(file_path)?;
+		
+		Ok(file)
+	}
+	
+	/// Add a generated output image, then return its image id.
+	/// [file_name] is the name of the written output file, [background] is the file name of the
+	/// background that the image was generated from.
+	pub fn add_image(&mut self, id: u32, width: u32, height: u32, file_name: String, background: String, difficulty: Option<Difficulty>) -> u32 {
+		let datetime: DateTime<Local> = SystemTime::now().into();
+		
+		self.unjournaled.insert(id, (self.file.images.len(), self.file.annotations.len()));
 		self.file.images.push(CocoImage {
-			id: self.image_id,
+			id,
 			license: None,
 			coco_url: None,
 			flickr_url: None,
 			width,
 			height,
 			file_name,
-			date_captured,
+			date_captured: datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+			background: Some(background),
+			difficulty,
 		});
-		self.image_id += 1;
 		
-		self.image_id
+		id
 	}
 	
+	/// Add an annotation to an image that was previously added, then return the annotation id
 	pub fn add_annotation(&mut self, image_id: u32, category_id: u32, iscrowd: u8, segmentation: Vec<Vec<f32>>, area: f64, bbox: BoundingBox) -> u32 {
+		let id = self.annotation_id;
+		
 		self.file.annotations.push(CocoAnnotation {
-			id: Some(self.annotation_id),
+			id: Some(id),
 			image_id,
 			category_id,
 			iscrowd,
 			segmentation,
 			area,
 			bbox,
+			attributes: Attributes::new(),
+			ignore: false,
 		});
 		self.annotation_id += 1;
 		
-		self.annotation_id
+		id
+	}
+	
+	/// Add an annotation with free-form attributes, see [CocoGenerator::add_annotation]
+	pub fn add_annotation_with_attributes(&mut self, image_id: u32, category_id: u32, area: f64, bbox: BoundingBox, attributes: Attributes) -> u32 {
+		let id = self.add_annotation(image_id, category_id, 0, vec![], area, bbox);
+		self.file.annotations.last_mut().unwrap().attributes = attributes;
+		
+		id
+	}
+	
+	/// Add the annotation of an object placed onto an image, see [CocoGenerator::add_annotation]
+	pub fn add_placement(&mut self, image_id: u32, placement: &Placement) -> u32 {
+		let id = self.add_annotation_with_attributes(image_id, placement.object_class, placement.area(), placement.bbox, placement.attributes.clone());
+		self.file.annotations.last_mut().unwrap().ignore = placement.ignore;
+		
+		id
 	}
 }
 
@@ -93,6 +246,49 @@ pub struct CocoFormatFile {
 	categories: Vec<CocoCategory>,
 }
 
+impl CocoFormatFile {
+	/// Read a previously written annotations file back from disk
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, GenerationError> {
+		let file = std::fs::read_to_string(path)?;
+		
+		Ok(serde_json::from_str(&file)?)
+	}
+	
+	pub fn images(&self) -> &Vec<CocoImage> {
+		&self.images
+	}
+	
+	pub fn annotations(&self) -> &Vec<CocoAnnotation> {
+		&self.annotations
+	}
+	
+	pub fn categories(&self) -> &Vec<CocoCategory> {
+		&self.categories
+	}
+	
+	pub fn images_mut(&mut self) -> &mut Vec<CocoImage> {
+		&mut self.images
+	}
+	
+	/// Write the file to disk
+	pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GenerationError> {
+		serde_json::to_writer_pretty(File::create(path)?, self)?;
+		
+		Ok(())
+	}
+	
+	/// A copy of this file that only holds the given images and their annotations
+	pub fn subset(&self, image_ids: &HashSet<u32>) -> Self {
+		Self {
+			info: self.info.clone(),
+			licenses: self.licenses.clone(),
+			images: self.images.iter().filter(|image| image_ids.contains(&image.id)).cloned().collect(),
+			annotations: self.annotations.iter().filter(|annotation| image_ids.contains(&annotation.image_id)).cloned().collect(),
+			categories: self.categories.clone(),
+		}
+	}
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct CocoFormatInfo {
 	description: String,
@@ -137,7 +333,43 @@ pub struct CocoImage {
 	width: u32,
 	height: u32,
 	file_name: String,
-	date_captured: String, // format "YYYY-MM-DD HH:MM:SS" ?
+	date_captured: String, // format "YYYY-MM-DD HH:MM:SS"
+	/// the source background the image was generated from, not part of the COCO spec
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	background: Option<String>,
+	/// how hard the image is to label, not part of the COCO spec
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	difficulty: Option<Difficulty>,
+}
+
+impl CocoImage {
+	pub fn id(&self) -> u32 {
+		self.id
+	}
+	
+	pub fn width(&self) -> u32 {
+		self.width
+	}
+	
+	pub fn height(&self) -> u32 {
+		self.height
+	}
+	
+	pub fn file_name(&self) -> &str {
+		&self.file_name
+	}
+	
+	pub fn set_file_name(&mut self, file_name: String) {
+		self.file_name = file_name;
+	}
+	
+	pub fn background(&self) -> Option<&str> {
+		self.background.as_deref()
+	}
+	
+	pub fn difficulty(&self) -> Option<&Difficulty> {
+		self.difficulty.as_ref()
+	}
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -150,6 +382,42 @@ pub struct CocoAnnotation {
 	segmentation: Vec<Vec<f32>>,
 	area: f64,
 	bbox: BoundingBox,
+	/// free-form attributes of the object, as written by CVAT
+	#[serde(default, skip_serializing_if = "Attributes::is_empty")]
+	attributes: Attributes,
+	/// an object of non-interest that should not be learned or evaluated on
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	ignore: bool,
+}
+
+impl CocoAnnotation {
+	pub fn id(&self) -> Option<u32> {
+		self.id
+	}
+	
+	pub fn image_id(&self) -> u32 {
+		self.image_id
+	}
+	
+	pub fn category_id(&self) -> u32 {
+		self.category_id
+	}
+	
+	pub fn area(&self) -> f64 {
+		self.area
+	}
+	
+	pub fn bbox(&self) -> BoundingBox {
+		self.bbox
+	}
+	
+	pub fn attributes(&self) -> &Attributes {
+		&self.attributes
+	}
+	
+	pub fn ignore(&self) -> bool {
+		self.ignore
+	}
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -168,6 +436,14 @@ impl CocoCategory {
 			supercategory: None,
 		}
 	}
+	
+	pub fn id(&self) -> u32 {
+		self.id
+	}
+	
+	pub fn name(&self) -> &str {
+		&self.name
+	}
 }
 
 pub trait CocoCategoryInfo {
@@ -184,4 +460,126 @@ fn test_collision_detector() {
 	assert!(!a.collides_with(&c));
 	assert!(b.collides_with(&a));
 	assert!(a.collides_with(&a));
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_recover_from_journal() {
+	use crate::generator::util;
+	
+	let dir = util::test_dir("recover_from_journal");
+	let annotations_path = dir.join("annotations.json");
+	
+	let mut coco = CocoGenerator::new(&annotations_path, vec![CocoCategory::new(1, "tire".to_string())]);
+	for i in 0..3 {
+		let id = coco.add_image(i, 100, 100, format!("{}.png", i), "grass.png".to_string(), None);
+		coco.add_annotation(id, 1, 0, vec![], 100.0, BoundingBox { x: i, y: i, width: 10, height: 10 });
+		coco.journal_image(id).unwrap();
+	}
+	assert!(coco.journal_image(1).is_err());
+	
+	// simulate a crash in the middle of writing the next entry
+	let journal_path = AnnotationJournal::journal_path(&annotations_path);
+	let mut journal = OpenOptions::new().append(true).open(&journal_path).unwrap();
+	std::io::Write::write_all(&mut journal, b"{\"entry\":\"image\",\"image\":{\"id\":3,").unwrap();
+	drop(coco);
+	
+	let recovered = CocoGenerator::recover(&annotations_path).unwrap();
+	assert_eq!(recovered.images().len(), 3);
+	assert_eq!(recovered.annotations().len(), 3);
+	assert_eq!(recovered.categories().len(), 1);
+	assert_eq!(CocoFormatFile::load(&annotations_path).unwrap(), recovered);
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_annotations_resolve() {
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("annotations_resolve");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.permit_collisions = true;
+	tg.config.worker_threads = 4;
+	tg.generate_targets(12, ..4, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	assert_eq!(file.images().len(), 12);
+	assert!(!file.annotations().is_empty());
+	
+	for image in file.images() {
+		assert_eq!(image.file_name(), format!("{}.png", image.id()));
+		assert!(image.background().is_some());
+		
+		let written = image::open(output.join(image.file_name())).unwrap();
+		assert_eq!((written.width(), written.height()), (image.width(), image.height()));
+	}
+	
+	for annotation in file.annotations() {
+		assert!(file.images().iter().any(|image| image.id() == annotation.image_id()));
+		assert!(file.categories().iter().any(|category| category.id() == annotation.category_id()));
+	}
+	
+	let mut names = file.categories().iter().map(|c| c.name()).collect::<Vec<&str>>();
+	names.sort();
+	assert_eq!(names, vec!["car", "tire"]);
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_resume_generation() {
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("resume_generation");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	let annotations_path = output.join("annotations.json");
+	
+	let mut tg = TargetGenerator::new(&backgrounds, &objects, &annotations_path).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.generate_targets(4, ..4, &output).unwrap();
+	drop(tg); // interrupted before close
+	
+	// an image that was never finished
+	let unfinished = std::fs::read(output.join("2.png")).unwrap();
+	std::fs::remove_file(output.join("2.png")).unwrap();
+	let first = std::fs::read(output.join("0.png")).unwrap();
+	
+	let mut tg = TargetGenerator::new(&backgrounds, &objects, &annotations_path).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.resume = true;
+	tg.config.export_cvat = true;
+	assert!(tg.generate_targets(4, ..4, &output).is_err());
+	tg.config.export_cvat = false;
+	
+	// the missing images would be planned differently with other parameters, so they are rejected
+	assert!(tg.generate_targets(8, ..4, &output).is_err());
+	assert!(tg.generate_targets(4, ..3, &output).is_err());
+	tg.config.negative_ratio = 0.5;
+	assert!(tg.generate_targets(4, ..4, &output).is_err());
+	tg.config.negative_ratio = 0.0;
+	tg.generate_targets(4, ..4, &output).unwrap();
+	tg.close();
+	
+	// the seed of the first run is picked up from the journal, so the missing image comes out the same
+	assert_eq!(std::fs::read(output.join("0.png")).unwrap(), first);
+	assert_eq!(std::fs::read(output.join("2.png")).unwrap(), unfinished);
+	
+	let file = CocoFormatFile::load(&annotations_path).unwrap();
+	assert_eq!(file.images().iter().map(|image| image.id()).collect::<Vec<u32>>(), (0..4).collect::<Vec<u32>>());
+	
+	let mut ids = file.annotations().iter().map(|annotation| annotation.id().unwrap()).collect::<Vec<u32>>();
+	ids.sort();
+	assert_eq!(ids, (0..file.annotations().len() as u32).collect::<Vec<u32>>());
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -1,12 +1,54 @@
 use image::Rgba;
+use serde::{Deserialize, Serialize, Serializer};
+use crate::generator::clutter::ClutterConfig;
+use crate::generator::naming::OutputNaming;
+use crate::generator::split::SplitConfig;
+use crate::objects::NADIR;
+use crate::standard_targets::StandardTargetConfig;
+
+/// How the objects placed in each image are chosen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectSampling {
+	/// every sprite is equally likely, so classes with more sprites show up more often
+	#[default]
+	Sprite,
+	/// a class is chosen by the weights in objects.json, then one of its sprites
+	Class,
+	/// the classes of the whole run are planned up front so that the number of objects of each class
+	/// across the run matches the weights in objects.json
+	BalancedRun,
+}
+
+/// How many objects of non-interest are placed in each image, see [crate::generator::TargetGenerator::load_distractors]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct DistractorConfig {
+	/// the fewest distractors placed in an image, on top of the regular objects
+	pub min_count: u32,
+	/// the most distractors placed in an image
+	pub max_count: u32,
+	/// annotate the distractors with their own classes and an ignore flag instead of leaving them out
+	pub annotate: bool,
+}
+
+impl Default for DistractorConfig {
+	fn default() -> Self {
+		Self {
+			min_count: 0,
+			max_count: 3,
+			annotate: false,
+		}
+	}
+}
 
 /// The config values for generating target images. Setting these values is optional, they will default 
 /// to the predefined values.
-#[derive(Debug, Clone, PartialEq, Copy)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct TargetGeneratorConfig {
 	/// whether or not to visualize the bounding boxes of the objects
 	pub visualize_bboxes: bool,
 	/// the color to use for the maskover effect, which basically fills the bounding box with a color
+	#[serde(serialize_with = "serialize_color")]
 	pub maskover_color: Option<Rgba<u8>>,
 	/// whether or not to allow duplicates of the same object within the same generated target image
 	pub permit_duplicates: bool,
@@ -18,8 +60,56 @@ pub struct TargetGeneratorConfig {
 	pub worker_threads: u8,
 	/// Whether or not to compress the generated target images
 	pub compress: bool,
+	/// The seed of the run. Each image draws every random choice from an RNG seeded with this seed and
+	/// its index, so the same seed reproduces the same images no matter how many worker threads are
+	/// used. A random seed is chosen for each run if not set, a resumed run keeps the seed of the run it
+	/// continues.
+	pub seed: Option<u64>,
+	/// How to choose the objects that are placed in each image
+	pub object_sampling: ObjectSampling,
 	/// Should the objects be randomly rotated (currently only supports 90 degree rotations)
 	pub do_random_rotation: bool,
+	/// Whether or not to write a JSON sidecar per image with every sampled generation parameter, in a "provenance" folder
+	pub write_provenance: bool,
+	/// Whether or not to also write KITTI labels, one .txt file per image in a "kitti" folder
+	pub export_kitti: bool,
+	/// Whether or not to also write a single CVAT for images XML file
+	pub export_cvat: bool,
+	/// Whether or not to also write LabelMe JSON files, one per image in a "labelme" folder
+	pub export_labelme: bool,
+	/// Whether or not to also write YOLO labels, one .txt file per image in a "yolo" folder
+	pub export_yolo: bool,
+	/// The fraction of images that are generated without any objects, as negatives that only show the
+	/// background. They are registered without annotations and get empty label files.
+	pub negative_ratio: f32,
+	/// The largest sigma of the gaussian blur applied to each image, a sigma is sampled per image up to this
+	pub max_blur_sigma: f32,
+	/// The largest standard deviation of the gaussian noise added to each image, sampled per image up to this
+	pub max_noise_sigma: f32,
+	/// Ramp the difficulty up over the indices of the run instead of sampling it, so that the first
+	/// images are the easiest and the last ones the hardest. Later images have smaller objects and are
+	/// blurred and noised closer to [TargetGeneratorConfig::max_blur_sigma] and [TargetGeneratorConfig::max_noise_sigma].
+	pub curriculum: bool,
+	/// If set, procedural SUAS standard targets are placed in each image on top of the objects
+	pub standard_targets: Option<StandardTargetConfig>,
+	/// The lowest angle of the camera above the horizon in degrees, an angle is sampled per image up to 90
+	/// (straight down). Objects with several views show the one closest to the sampled angle.
+	pub min_camera_elevation: f32,
+	/// If set, runway markings, roads, skid marks and debris are drawn onto each background at their
+	/// real size before the objects are placed
+	pub clutter: Option<ClutterConfig>,
+	/// If set, objects of non-interest from the distractor library are placed beneath the objects of each image
+	pub distractors: Option<DistractorConfig>,
+	/// How the generated images are named and laid out in the output folder
+	pub naming: OutputNaming,
+	/// If set, the generated images are divided into train/val/test folders with their own COCO files
+	/// once generation is finished
+	pub splits: Option<SplitConfig>,
+	/// Continue an interrupted run in the same output folder, only generating the images that are not
+	/// complete yet. The amount, object count range, [negative_ratio], [object_sampling] and [curriculum]
+	/// must be the same as in the interrupted run. Cannot be combined with [export_cvat], the CVAT file
+	/// of the interrupted run is never written so the images from before resuming could not be included in it.
+	pub resume: bool,
 }
 
 impl Default for TargetGeneratorConfig {
@@ -32,7 +122,29 @@ impl Default for TargetGeneratorConfig {
 			cache_size: 10,
 			worker_threads: 15,
 			compress: true,
-			do_random_rotation: true
+			seed: None,
+			object_sampling: ObjectSampling::Sprite,
+			do_random_rotation: true,
+			write_provenance: false,
+			export_kitti: false,
+			export_cvat: false,
+			export_labelme: false,
+			export_yolo: false,
+			negative_ratio: 0.0,
+			max_blur_sigma: 0.0,
+			max_noise_sigma: 0.0,
+			curriculum: false,
+			standard_targets: None,
+			distractors: None,
+			clutter: None,
+			min_camera_elevation: NADIR,
+			naming: OutputNaming::default(),
+			splits: None,
+			resume: false,
 		}
 	}
-}
\ No newline at end of file
+}
+
+fn serialize_color<S: Serializer>(color: &Option<Rgba<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+	color.map(|c| c.0).serialize(serializer)
+}
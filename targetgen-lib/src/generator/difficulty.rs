@@ -0,0 +1,181 @@
+use image::{Rgba, RgbaImage};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::generator::placement::{ObjectProvenance, Placement};
+
+/// Objects whose shorter side is at least this many pixels do not add to the difficulty
+const EASY_OBJECT_SIZE: f32 = 64.0;
+/// Objects whose shorter side is at most this many pixels are as hard as it gets
+const HARD_OBJECT_SIZE: f32 = 8.0;
+/// The gaussian blur sigma that counts as fully degraded
+const HARDEST_BLUR_SIGMA: f32 = 3.0;
+/// The standard deviation of the gaussian noise that counts as fully degraded
+const HARDEST_NOISE_SIGMA: f32 = 25.0;
+
+/// How hard a generated image is to label, from the factors the generator knows about. Each factor is
+/// normalized to 0.0 (easy) to 1.0 (hard) and [Difficulty::score] is their weighted sum.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Difficulty {
+	/// the overall difficulty, from 0.0 to 1.0
+	pub score: f32,
+	/// the shorter side in pixels of the visible part of the smallest object, none without objects
+	pub smallest_object: Option<u32>,
+	/// the lowest contrast between an object and the background around it, see [local_contrast]
+	pub lowest_contrast: Option<f32>,
+	/// the largest occlusion or truncation of any object
+	pub occlusion: f32,
+	/// the sigma of the gaussian blur that was applied to the whole image
+	pub blur_sigma: f32,
+	/// the standard deviation of the gaussian noise that was added to the whole image
+	pub noise_sigma: f32,
+}
+
+impl Difficulty {
+	/// Score an image from its placements and the provenance of its objects, in the same order
+	/// Distractors are not targets, so they do not make an image harder to learn from.
+	pub fn compute(placements: &[Placement], objects: &[ObjectProvenance], image_width: u32, image_height: u32, blur_sigma: f32, noise_sigma: f32) -> Self {
+		let placements = placements.iter().filter(|p| !p.ignore).collect::<Vec<_>>();
+		let smallest_object = placements.iter()
+			.filter_map(|p| p.visible_bbox(image_width, image_height))
+			.map(|b| b.width.min(b.height))
+			.min();
+		let lowest_contrast = objects.iter().filter(|o| !o.distractor).map(|o| o.contrast).reduce(f32::min);
+		let occlusion = placements.iter().map(|p| p.occlusion.max(p.truncation)).fold(0.0, f32::max);
+		
+		let size = smallest_object.map(|s| (EASY_OBJECT_SIZE - s as f32) / (EASY_OBJECT_SIZE - HARD_OBJECT_SIZE)).unwrap_or(0.0).clamp(0.0, 1.0);
+		let contrast = lowest_contrast.map(|c| 1.0 - c).unwrap_or(0.0).clamp(0.0, 1.0);
+		let degradation = (blur_sigma / HARDEST_BLUR_SIGMA).max(noise_sigma / HARDEST_NOISE_SIGMA).min(1.0);
+		
+		Self {
+			score: 0.3 * size + 0.3 * contrast + 0.2 * occlusion + 0.2 * degradation,
+			smallest_object,
+			lowest_contrast,
+			occlusion,
+			blur_sigma,
+			noise_sigma,
+		}
+	}
+}
+
+/// The perceived brightness of a pixel, from 0.0 to 255.0
+fn luminance(pixel: &Rgba<u8>) -> f32 {
+	0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+/// The difference in mean luminance between the opaque pixels of [object] and the patch of [background]
+/// around where it is pasted at ([x], [y]), from 0.0 (same brightness) to 1.0 (black on white). The patch
+/// extends half of the object's longer side past each of its edges.
+pub fn local_contrast(background: &RgbaImage, object: &RgbaImage, x: u32, y: u32) -> f32 {
+	let opaque = object.pixels().filter(|p| p[3] > 127).collect::<Vec<&Rgba<u8>>>();
+	if opaque.is_empty() {
+		return 0.0;
+	}
+	let object_luminance = opaque.iter().map(|p| luminance(p)).sum::<f32>() / opaque.len() as f32;
+	
+	let margin = object.width().max(object.height()) / 2;
+	let (left, top) = (x.saturating_sub(margin), y.saturating_sub(margin));
+	let right = (x + object.width() + margin).min(background.width());
+	let bottom = (y + object.height() + margin).min(background.height());
+	let inside = |px: u32, py: u32| px >= x && px < x + object.width() && py >= y && py < y + object.height();
+	
+	let (mut sum, mut count) = (0.0, 0);
+	for py in top..bottom {
+		for px in left..right {
+			if !inside(px, py) {
+				sum += luminance(background.get_pixel(px, py));
+				count += 1;
+			}
+		}
+	}
+	
+	if count == 0 {
+		return 1.0; // the object covers the whole image, nothing to blend into
+	}
+	
+	(object_luminance - sum / count as f32).abs() / 255.0
+}
+
+/// Add gaussian noise with a standard deviation of [sigma] to the color channels of an image
+pub fn add_gaussian_noise<R: Rng + ?Sized>(image: &mut RgbaImage, sigma: f32, rng: &mut R) {
+	for pixel in image.pixels_mut() {
+		for channel in 0..3 {
+			// Box-Muller transform
+			let (u1, u2): (f32, f32) = (rng.gen_range(f32::EPSILON..1.0), rng.r#gen());
+			let noise = (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos() * sigma;
+			pixel[channel] = (pixel[channel] as f32 + noise).round().clamp(0.0, 255.0) as u8;
+		}
+	}
+}
+
+#[test]
+fn test_difficulty() {
+	use crate::generator::coco::BoundingBox;
+	
+	let mut background = RgbaImage::from_pixel(100, 100, Rgba([255, 255, 255, 255]));
+	let dark = RgbaImage::from_pixel(10, 10, Rgba([0, 0, 0, 255]));
+	let light = RgbaImage::from_pixel(10, 10, Rgba([250, 250, 250, 255]));
+	assert_eq!(local_contrast(&background, &dark, 40, 40), 1.0);
+	assert!(local_contrast(&background, &light, 40, 40) < 0.05);
+	
+	// the patch around the object counts, not the pixels underneath it
+	image::imageops::overlay(&mut background, &dark, 40, 40);
+	assert_eq!(local_contrast(&background, &dark, 40, 40), 1.0);
+	
+	let object = |contrast| ObjectProvenance { contrast, ..Default::default() };
+	let easy = Difficulty::compute(
+		&[Placement::new(0, BoundingBox { x: 0, y: 0, width: 80, height: 80 }, 100, 100)],
+		&[object(1.0)], 100, 100, 0.0, 0.0,
+	);
+	assert_eq!(easy.score, 0.0);
+	assert_eq!(easy.smallest_object, Some(80));
+	
+	let hard = Difficulty::compute(
+		&[
+			Placement::new(0, BoundingBox { x: 0, y: 0, width: 80, height: 80 }, 100, 100),
+			Placement::new(0, BoundingBox { x: 95, y: 95, width: 10, height: 10 }, 100, 100),
+		],
+		&[object(1.0), object(0.0)], 100, 100, 3.0, 0.0,
+	);
+	assert_eq!(hard.smallest_object, Some(5));
+	assert_eq!(hard.occlusion, 0.75);
+	assert!((hard.score - 0.95).abs() < 1e-6);
+	
+	let negative = Difficulty::compute(&[], &[], 100, 100, 0.0, 12.5);
+	assert_eq!((negative.smallest_object, negative.lowest_contrast), (None, None));
+	assert!((negative.score - 0.1).abs() < 1e-6);
+}
+
+#[test]
+fn test_curriculum() {
+	use crate::generator::coco::CocoFormatFile;
+	use crate::generator::export::provenance::ProvenanceExporter;
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("curriculum");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.max_blur_sigma = 2.0;
+	tg.config.max_noise_sigma = 10.0;
+	tg.config.curriculum = true;
+	tg.config.write_provenance = true;
+	tg.generate_targets(5, ..3, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	let difficulties = file.images().iter().map(|image| image.difficulty().unwrap().clone()).collect::<Vec<Difficulty>>();
+	assert_eq!(difficulties.iter().map(|d| d.blur_sigma).collect::<Vec<f32>>(), vec![0.0, 0.5, 1.0, 1.5, 2.0]);
+	assert_eq!(difficulties.iter().map(|d| d.noise_sigma).collect::<Vec<f32>>(), vec![0.0, 2.5, 5.0, 7.5, 10.0]);
+	assert!(difficulties.iter().all(|d| (0.0..=1.0).contains(&d.score)));
+	
+	let first = ProvenanceExporter::load(output.join("provenance"), "0.png").unwrap();
+	let last = ProvenanceExporter::load(output.join("provenance"), "4.png").unwrap();
+	assert!(first.pixels_per_meter > last.pixels_per_meter);
+	assert_eq!(last.curriculum_level, Some(1.0));
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -16,6 +16,8 @@ pub enum GenerationError {
 	NotEnoughObjectsAvailable,
 	#[error("Too many collisions occurred while generating objects, try reducing the number of objects")]
 	TooManyCollisions,
+	#[error("Multiple images are named {0}")]
+	FileNameCollision(String),
 
 	// conversions
 	#[error("Error parsing integer")]
@@ -0,0 +1,272 @@
+// https://docs.cvat.ai/docs/manual/advanced/formats/format-cvat/
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use crate::generator::error::GenerationError;
+use crate::generator::export::LabelExporter;
+use crate::generator::placement::GeneratedTarget;
+
+/// The name of the CVAT file in the output folder
+pub const CVAT_FILE: &str = "cvat_annotations.xml";
+
+/// Writes a single "CVAT for images 1.1" XML file that holds every generated image. Images are
+/// collected as they are generated and the file is written by [LabelExporter::finish].
+pub struct CvatExporter {
+	file_path: PathBuf,
+	names: HashMap<u32, String>,
+	images: Mutex<Vec<(u32, String)>>,
+	/// names of the attributes seen per class, declared on the labels of the document
+	attributes: Mutex<HashMap<u32, BTreeSet<String>>>,
+}
+
+impl CvatExporter {
+	pub fn new<P: AsRef<Path>>(file_path: P, names: HashMap<u32, String>) -> Self {
+		Self {
+			file_path: file_path.as_ref().to_path_buf(),
+			names,
+			images: Mutex::new(vec![]),
+			attributes: Mutex::new(HashMap::new()),
+		}
+	}
+	
+	fn name(&self, object_class: u32) -> String {
+		self.names.get(&object_class).cloned().unwrap_or(object_class.to_string())
+	}
+	
+	/// Produce the <image> element for a single generated image
+	pub fn image_element(&self, image_id: u32, file_name: &str, target: &GeneratedTarget) -> String {
+		let (width, height) = (target.image.width(), target.image.height());
+		let mut xml = String::new();
+		
+		writeln!(xml, "  <image id=\"{}\" name=\"{}\" width=\"{}\" height=\"{}\">", image_id, escape(file_name), width, height).unwrap();
+		
+		for (z_order, placement) in target.placements.iter().enumerate() {
+			// CVAT boxes have no ignore flag, so ignored distractors are left out
+			let Some(visible) = placement.visible_bbox(width, height).filter(|_| !placement.ignore) else {
+				continue;
+			};
+			
+			writeln!(xml, "    <box label=\"{}\" source=\"auto\" occluded=\"{}\" xtl=\"{:.2}\" ytl=\"{:.2}\" xbr=\"{:.2}\" ybr=\"{:.2}\" z_order=\"{}\">",
+				escape(&self.name(placement.object_class)),
+				(placement.occlusion > 0.0) as u8,
+				visible.x as f32,
+				visible.y as f32,
+				(visible.x + visible.width) as f32,
+				(visible.y + visible.height) as f32,
+				z_order,
+			).unwrap();
+			for (name, value) in &placement.attributes {
+				writeln!(xml, "      <attribute name=\"{}\">{}</attribute>", escape(name), escape(&attribute_value(value))).unwrap();
+			}
+			writeln!(xml, "    </box>").unwrap();
+		}
+		
+		writeln!(xml, "  </image>").unwrap();
+		
+		xml
+	}
+	
+	/// Produce the complete XML document from the images collected so far
+	pub fn document(&self) -> String {
+		let mut images = self.images.lock().unwrap().clone();
+		images.sort_by_key(|(id, _)| *id);
+		
+		let mut labels = self.names.iter().collect::<Vec<(&u32, &String)>>();
+		labels.sort();
+		let attributes = self.attributes.lock().unwrap();
+		
+		let mut xml = String::new();
+		writeln!(xml, "<?xml version=\"1.0\" encoding=\"utf-8\"?>").unwrap();
+		writeln!(xml, "<annotations>").unwrap();
+		writeln!(xml, "  <version>1.1</version>").unwrap();
+		writeln!(xml, "  <meta>").unwrap();
+		writeln!(xml, "    <task>").unwrap();
+		writeln!(xml, "      <name>Auto Generated Dataset</name>").unwrap();
+		writeln!(xml, "      <size>{}</size>", images.len()).unwrap();
+		writeln!(xml, "      <mode>annotation</mode>").unwrap();
+		writeln!(xml, "      <labels>").unwrap();
+		for (class, name) in labels {
+			writeln!(xml, "        <label>").unwrap();
+			writeln!(xml, "          <name>{}</name>", escape(name)).unwrap();
+			writeln!(xml, "          <type>rectangle</type>").unwrap();
+			writeln!(xml, "          <attributes>").unwrap();
+			for attribute in attributes.get(class).into_iter().flatten() {
+				writeln!(xml, "            <attribute>").unwrap();
+				writeln!(xml, "              <name>{}</name>", escape(attribute)).unwrap();
+				writeln!(xml, "              <mutable>False</mutable>").unwrap();
+				writeln!(xml, "              <input_type>text</input_type>").unwrap();
+				writeln!(xml, "              <default_value></default_value>").unwrap();
+				writeln!(xml, "              <values></values>").unwrap();
+				writeln!(xml, "            </attribute>").unwrap();
+			}
+			writeln!(xml, "          </attributes>").unwrap();
+			writeln!(xml, "        </label>").unwrap();
+		}
+		writeln!(xml, "      </labels>").unwrap();
+		writeln!(xml, "    </task>").unwrap();
+		writeln!(xml, "  </meta>").unwrap();
+		for (_, image) in images {
+			xml.push_str(&image);
+		}
+		writeln!(xml, "</annotations>").unwrap();
+		
+		xml
+	}
+}
+
+impl LabelExporter for CvatExporter {
+	fn export(&self, image_id: u32, file_name: &str, target: &GeneratedTarget) -> Result<(), GenerationError> {
+		let element = self.image_element(image_id, file_name, target);
+		
+		let mut attributes = self.attributes.lock().unwrap();
+		for placement in &target.placements {
+			attributes.entry(placement.object_class).or_default().extend(placement.attributes.keys().cloned());
+		}
+		drop(attributes);
+		
+		self.images.lock().unwrap().push((image_id, element));
+		
+		Ok(())
+	}
+	
+	fn finish(&self) -> Result<(), GenerationError> {
+		std::fs::write(&self.file_path, self.document())?;
+		
+		Ok(())
+	}
+}
+
+/// Point the images of a CVAT file at new file names, ex. once they have been moved into their split.
+/// [names] maps the ids of the images to rename to their new names, every one of them must be in the file.
+pub fn rename_images(path: &Path, names: &HashMap<u32, String>) -> Result<(), GenerationError> {
+	let xml = std::fs::read_to_string(path)?;
+	let invalid = |reason: &str| GenerationError::GenericError(format!("Cannot rename the images of {}, {}", path.display(), reason));
+	let mut renamed = String::with_capacity(xml.len());
+	let mut missing = names.keys().copied().collect::<BTreeSet<u32>>();
+	let mut rest = xml.as_str();
+	
+	while let Some(start) = find_start_tag(rest, "image") {
+		let end = start + rest[start..].find('>').ok_or_else(|| invalid("an <image> tag is not closed"))?;
+		let tag_start = start + "<image".len();
+		let attributes = tag_attributes(&rest[tag_start..end]).ok_or_else(|| invalid("an <image> tag has malformed attributes"))?;
+		let id = attributes.iter().find(|(name, _)| *name == "id").and_then(|(_, value)| rest[tag_start + value.start..tag_start + value.end].parse::<u32>().ok());
+		let value = attributes.iter().find(|(name, _)| *name == "name").map(|(_, value)| tag_start + value.start..tag_start + value.end);
+		
+		match (id.and_then(|id| Some((id, names.get(&id)?))), value) {
+			(Some((id, name)), Some(value)) => {
+				renamed.push_str(&rest[..value.start]);
+				renamed.push_str(&escape(name));
+				renamed.push_str(&rest[value.end..end]);
+				missing.remove(&id);
+			}
+			_ => renamed.push_str(&rest[..end]),
+		}
+		rest = &rest[end..];
+	}
+	renamed.push_str(rest);
+	
+	if !missing.is_empty() {
+		return Err(invalid(&format!("it has no images with the ids {:?}", missing)));
+	}
+	
+	std::fs::write(path, renamed)?;
+	
+	Ok(())
+}
+
+/// The position of the next start tag of an element, ex. `<image ...>` but not `<images>` or `</image>`
+fn find_start_tag(xml: &str, element: &str) -> Option<usize> {
+	let tag = format!("<{}", element);
+	let mut offset = 0;
+	
+	while let Some(found) = xml[offset..].find(&tag) {
+		let start = offset + found;
+		match xml[start + tag.len()..].chars().next() {
+			Some(c) if c.is_whitespace() || c == '>' || c == '/' => return Some(start),
+			_ => offset = start + tag.len(),
+		}
+	}
+	
+	None
+}
+
+/// The attributes of a tag, given the text between its element name and its closing `>`, as their names
+/// along with the range of their still escaped values within the text. None if they cannot be parsed.
+fn tag_attributes(tag: &str) -> Option<Vec<(&str, std::ops::Range<usize>)>> {
+	let mut attributes = vec![];
+	let mut position = 0;
+	
+	loop {
+		position += tag[position..].len() - tag[position..].trim_start().len();
+		if position == tag.len() || tag[position..].starts_with('/') {
+			return Some(attributes);
+		}
+		
+		let equals = position + tag[position..].find('=')?;
+		let name = tag[position..equals].trim();
+		let quote_position = equals + 1 + tag[equals + 1..].len() - tag[equals + 1..].trim_start().len();
+		let quote = tag[quote_position..].chars().next().filter(|c| *c == '"' || *c == '\'')?;
+		let value_start = quote_position + 1;
+		let value_end = value_start + tag[value_start..].find(quote)?;
+		
+		attributes.push((name, value_start..value_end));
+		position = value_end + 1;
+	}
+}
+
+/// The text of an attribute, strings are written without their quotes
+fn attribute_value(value: &serde_json::Value) -> String {
+	match value {
+		serde_json::Value::String(s) => s.clone(),
+		value => value.to_string(),
+	}
+}
+
+/// Escape the characters that are not allowed in XML text and attribute values
+fn escape(s: &str) -> String {
+	s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+#[test]
+fn test_cvat_document() {
+	use image::RgbaImage;
+	use crate::generator::coco::BoundingBox;
+	use crate::generator::placement::{Attributes, Placement};
+	
+	let exporter = CvatExporter::new("unused.xml", HashMap::from([(7, "car".to_string())]));
+	let mut placement = Placement::new(7, BoundingBox { x: 90, y: 10, width: 20, height: 10 }, 100, 100);
+	placement.attributes = Attributes::from([("color".to_string(), "red".into()), ("doors".to_string(), 4.into())]);
+	let target = GeneratedTarget {
+		image: RgbaImage::new(100, 100),
+		placements: vec![placement],
+		provenance: Default::default(),
+	};
+	
+	exporter.export(3, "3.png", &target).unwrap();
+	exporter.export(1, "1.png", &target).unwrap();
+	let xml = exporter.document();
+	
+	assert!(xml.contains("<size>2</size>"));
+	assert!(xml.find("name=\"1.png\"").unwrap() < xml.find("name=\"3.png\"").unwrap());
+	assert!(xml.contains("<box label=\"car\" source=\"auto\" occluded=\"0\" xtl=\"90.00\" ytl=\"10.00\" xbr=\"100.00\" ybr=\"20.00\" z_order=\"0\">"));
+	assert!(xml.contains("<attribute name=\"color\">red</attribute>"));
+	assert!(xml.contains("<attribute name=\"doors\">4</attribute>"));
+	assert!(xml.contains("<name>doors</name>"));
+	assert_eq!(escape("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+	
+	let dir = crate::generator::util::test_dir("cvat_rename");
+	std::fs::create_dir_all(&dir).unwrap();
+	std::fs::write(dir.join(CVAT_FILE), &xml).unwrap();
+	rename_images(&dir.join(CVAT_FILE), &HashMap::from([(3, "train/3.png".to_string())])).unwrap();
+	let renamed = std::fs::read_to_string(dir.join(CVAT_FILE)).unwrap();
+	assert_eq!(renamed, xml.replace("name=\"3.png\"", "name=\"train/3.png\""));
+	
+	// found by id wherever the attributes are, and every image to rename has to be in the file
+	std::fs::write(dir.join(CVAT_FILE), "<annotations>\n<image width='10' name='1.png'\n  id='1'/>\n</annotations>\n").unwrap();
+	rename_images(&dir.join(CVAT_FILE), &HashMap::from([(1, "val/1.png".to_string())])).unwrap();
+	assert_eq!(std::fs::read_to_string(dir.join(CVAT_FILE)).unwrap(), "<annotations>\n<image width='10' name='val/1.png'\n  id='1'/>\n</annotations>\n");
+	assert!(rename_images(&dir.join(CVAT_FILE), &HashMap::from([(2, "val/2.png".to_string())])).is_err());
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,110 @@
+// https://www.cvlibs.net/datasets/kitti/eval_object.php (see readme.txt of the object development kit)
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use crate::generator::error::GenerationError;
+use crate::generator::export::{create_label_file, label_path, LabelExporter};
+use crate::generator::placement::{GeneratedTarget, Placement};
+
+/// Writes KITTI object labels, one .txt file per image. Only the 2D fields are filled in, all of the
+/// 3D fields (alpha, dimensions, location and rotation_y) are zero.
+pub struct KittiExporter {
+	folder: PathBuf,
+	names: HashMap<u32, String>,
+}
+
+impl KittiExporter {
+	pub fn new<P: AsRef<Path>>(folder: P, names: HashMap<u32, String>) -> Result<Self, GenerationError> {
+		std::fs::create_dir_all(folder.as_ref())?;
+		
+		Ok(Self {
+			folder: folder.as_ref().to_path_buf(),
+			names,
+		})
+	}
+	
+	/// Produce a single label line for a placed object, ignored distractors are labelled as DontCare regions
+	pub fn label_line(&self, placement: &Placement, image_width: u32, image_height: u32) -> Option<String> {
+		let visible = placement.visible_bbox(image_width, image_height)?;
+		let name = if placement.ignore {
+			"DontCare".to_string()
+		} else {
+			self.names.get(&placement.object_class).cloned().unwrap_or(placement.object_class.to_string())
+		};
+		
+		Some(format!("{} {:.2} {} 0.00 {:.2} {:.2} {:.2} {:.2} 0.00 0.00 0.00 0.00 0.00 0.00 0.00",
+			name,
+			placement.truncation,
+			occlusion_level(placement.occlusion),
+			visible.x as f32,
+			visible.y as f32,
+			(visible.x + visible.width) as f32,
+			(visible.y + visible.height) as f32,
+		))
+	}
+}
+
+impl LabelExporter for KittiExporter {
+	fn export(&self, _image_id: u32, file_name: &str, target: &GeneratedTarget) -> Result<(), GenerationError> {
+		let mut labels = String::new();
+		
+		for placement in &target.placements {
+			if let Some(line) = self.label_line(placement, target.image.width(), target.image.height()) {
+				writeln!(labels, "{}", line).unwrap();
+			}
+		}
+		
+		create_label_file(&label_path(&self.folder, file_name, "txt"))?.write_all(labels.as_bytes())?;
+		
+		Ok(())
+	}
+}
+
+/// Convert an occluded fraction into a KITTI occlusion level:
+/// 0 = fully visible, 1 = partly occluded, 2 = largely occluded
+pub fn occlusion_level(occlusion: f32) -> u8 {
+	if occlusion <= 0.0 {
+		0
+	} else if occlusion <= 0.5 {
+		1
+	} else {
+		2
+	}
+}
+
+#[test]
+fn test_kitti_labels() {
+	use image::RgbaImage;
+	use crate::generator::coco::BoundingBox;
+	use crate::generator::placement::compute_occlusion;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("kitti_labels");
+	let exporter = KittiExporter::new(dir.join("kitti"), HashMap::from([(1, "tire".to_string()), (7, "car".to_string())])).unwrap();
+	
+	let mut placements = vec![
+		Placement::new(7, BoundingBox { x: 10, y: 10, width: 40, height: 20 }, 100, 100),
+		Placement::new(1, BoundingBox { x: 30, y: 10, width: 30, height: 30 }, 100, 100),
+		Placement::new(1, BoundingBox { x: 80, y: 90, width: 40, height: 20 }, 100, 100),
+	];
+	compute_occlusion(&mut placements);
+	
+	let target = GeneratedTarget {
+		image: RgbaImage::new(100, 100),
+		placements,
+		provenance: Default::default(),
+	};
+	exporter.export(4, "4.png", &target).unwrap();
+	
+	let labels = std::fs::read_to_string(dir.join("kitti").join("4.txt")).unwrap();
+	let lines = labels.lines().collect::<Vec<&str>>();
+	assert_eq!(lines, vec![
+		"car 0.00 1 0.00 10.00 10.00 50.00 30.00 0.00 0.00 0.00 0.00 0.00 0.00 0.00",
+		"tire 0.00 0 0.00 30.00 10.00 60.00 40.00 0.00 0.00 0.00 0.00 0.00 0.00 0.00",
+		"tire 0.75 0 0.00 80.00 90.00 100.00 100.00 0.00 0.00 0.00 0.00 0.00 0.00 0.00",
+	]);
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
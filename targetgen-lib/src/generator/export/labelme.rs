@@ -0,0 +1,117 @@
+// https://github.com/wkentaro/labelme
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::generator::error::GenerationError;
+use crate::generator::export::{create_label_file, label_path, LabelExporter};
+use crate::generator::placement::GeneratedTarget;
+
+const LABELME_VERSION: &str = "5.5.0";
+
+/// Writes LabelMe JSON files, one per image. The files are written to their own folder and refer to
+/// the generated image relative to that folder so that they can be opened in LabelMe directly.
+pub struct LabelMeExporter {
+	folder: PathBuf,
+	names: HashMap<u32, String>,
+}
+
+impl LabelMeExporter {
+	pub fn new<P: AsRef<Path>>(folder: P, names: HashMap<u32, String>) -> Result<Self, GenerationError> {
+		std::fs::create_dir_all(folder.as_ref())?;
+		
+		Ok(Self {
+			folder: folder.as_ref().to_path_buf(),
+			names,
+		})
+	}
+	
+	/// Produce the LabelMe file for a single generated image
+	pub fn labelme_file(&self, file_name: &str, target: &GeneratedTarget) -> LabelMeFile {
+		let (width, height) = (target.image.width(), target.image.height());
+		
+		let shapes = target.placements.iter().filter_map(|placement| {
+			let visible = placement.visible_bbox(width, height)?;
+			
+			Some(LabelMeShape {
+				label: self.names.get(&placement.object_class).cloned().unwrap_or(placement.object_class.to_string()),
+				points: vec![
+					[visible.x as f32, visible.y as f32],
+					[(visible.x + visible.width) as f32, (visible.y + visible.height) as f32],
+				],
+				group_id: None,
+				shape_type: "rectangle".to_string(),
+				// ignored distractors keep their label and are flagged instead
+				flags: if placement.ignore { HashMap::from([("ignore".to_string(), true)]) } else { HashMap::new() },
+			})
+		}).collect();
+		
+		LabelMeFile {
+			version: LABELME_VERSION.to_string(),
+			flags: HashMap::new(),
+			shapes,
+			// climb out of the labelme folder and any shard folder the file is in
+			image_path: format!("{}{}", "../".repeat(file_name.matches('/').count() + 1), file_name),
+			image_data: None,
+			image_height: height,
+			image_width: width,
+		}
+	}
+}
+
+impl LabelExporter for LabelMeExporter {
+	fn export(&self, _image_id: u32, file_name: &str, target: &GeneratedTarget) -> Result<(), GenerationError> {
+		let file = create_label_file(&label_path(&self.folder, file_name, "json"))?;
+		serde_json::to_writer_pretty(file, &self.labelme_file(file_name, target))?;
+		
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LabelMeFile {
+	version: String,
+	flags: HashMap<String, bool>,
+	shapes: Vec<LabelMeShape>,
+	image_path: String,
+	image_data: Option<String>,
+	image_height: u32,
+	image_width: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LabelMeShape {
+	label: String,
+	points: Vec<[f32; 2]>,
+	group_id: Option<u32>,
+	shape_type: String,
+	flags: HashMap<String, bool>,
+}
+
+#[test]
+fn test_labelme_file() {
+	use image::RgbaImage;
+	use crate::generator::coco::BoundingBox;
+	use crate::generator::placement::Placement;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("labelme_file");
+	let exporter = LabelMeExporter::new(&dir, HashMap::from([(1, "tire".to_string())])).unwrap();
+	let target = GeneratedTarget {
+		image: RgbaImage::new(100, 80),
+		placements: vec![Placement::new(1, BoundingBox { x: 5, y: 6, width: 10, height: 12 }, 100, 80)],
+		provenance: Default::default(),
+	};
+	
+	exporter.export(0, "0.png", &target).unwrap();
+	let file: LabelMeFile = serde_json::from_str(&std::fs::read_to_string(dir.join("0.json")).unwrap()).unwrap();
+	
+	assert_eq!(file.image_path, "../0.png");
+	assert_eq!((file.image_width, file.image_height), (100, 80));
+	assert_eq!(file.shapes.len(), 1);
+	assert_eq!(file.shapes[0].label, "tire");
+	assert_eq!(file.shapes[0].points, vec![[5.0, 6.0], [15.0, 18.0]]);
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
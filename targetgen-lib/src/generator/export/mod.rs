@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use crate::generator::coco::CocoCategory;
+use crate::generator::error::GenerationError;
+use crate::generator::placement::GeneratedTarget;
+
+pub mod kitti;
+pub mod cvat;
+pub mod labelme;
+pub mod provenance;
+pub mod yolo;
+
+/// An additional label format that is written alongside the COCO annotations as images are generated
+pub trait LabelExporter: Send + Sync {
+	/// Write the labels for a single generated image. [image_id] is the id the image was given in the
+	/// COCO annotations and [file_name] is the name of the written image, relative to the output folder.
+	fn export(&self, image_id: u32, file_name: &str, target: &GeneratedTarget) -> Result<(), GenerationError>;
+	
+	/// Called once after every image has been generated, for formats that are written as a single file
+	fn finish(&self) -> Result<(), GenerationError> {
+		Ok(())
+	}
+}
+
+/// Map category ids to their names for the formats that label objects by name
+pub fn category_names(categories: &[CocoCategory]) -> HashMap<u32, String> {
+	categories.iter().map(|category| (category.id(), category.name().to_string())).collect()
+}
+
+/// The path of the label file of an image inside of an exporter's [folder], which mirrors the path of
+/// the image relative to the output folder, ex. ("kitti", "0003/12.png", "txt") -> kitti/0003/12.txt
+pub fn label_path(folder: &Path, file_name: &str, extension: &str) -> PathBuf {
+	folder.join(file_name).with_extension(extension)
+}
+
+/// Create a label file at [label_path], along with any folders it needs
+pub(crate) fn create_label_file(path: &Path) -> Result<File, GenerationError> {
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+	
+	Ok(File::create(path)?)
+}
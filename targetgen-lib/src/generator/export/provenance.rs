@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+use crate::generator::error::GenerationError;
+use crate::generator::export::{create_label_file, label_path, LabelExporter};
+use crate::generator::placement::{GeneratedTarget, ImageProvenance};
+
+/// Writes a JSON sidecar per image that holds its [ImageProvenance], ex. 12.png -> provenance/12.json
+pub struct ProvenanceExporter {
+	folder: PathBuf,
+}
+
+impl ProvenanceExporter {
+	pub fn new<P: AsRef<Path>>(folder: P) -> Result<Self, GenerationError> {
+		std::fs::create_dir_all(folder.as_ref())?;
+		
+		Ok(Self {
+			folder: folder.as_ref().to_path_buf(),
+		})
+	}
+	
+	/// Read the sidecar of a previously generated image back from disk
+	pub fn load<P: AsRef<Path>>(folder: P, file_name: &str) -> Result<ImageProvenance, GenerationError> {
+		let file = std::fs::read_to_string(label_path(folder.as_ref(), file_name, "json"))?;
+		
+		Ok(serde_json::from_str(&file)?)
+	}
+}
+
+impl LabelExporter for ProvenanceExporter {
+	fn export(&self, _image_id: u32, file_name: &str, target: &GeneratedTarget) -> Result<(), GenerationError> {
+		let file = create_label_file(&label_path(&self.folder, file_name, "json"))?;
+		serde_json::to_writer_pretty(file, &target.provenance)?;
+		
+		Ok(())
+	}
+}
+
+#[test]
+fn test_provenance_reproduces_image() {
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("provenance_reproduces_image");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.compress = false;
+	tg.config.write_provenance = true;
+	tg.generate_targets(3, ..4, &output).unwrap();
+	tg.close();
+	
+	for i in 0..3 {
+		let file_name = format!("{}.png", i);
+		let provenance = ProvenanceExporter::load(output.join("provenance"), &file_name).unwrap();
+		assert!(provenance.background.ends_with("grass.png") || provenance.background.ends_with("runway.png"));
+		assert!(provenance.objects.iter().all(|o| o.sprite == "car_1.png" || o.sprite == "tire_1.png"));
+		
+		let mut regenerated = tg.generate_target(provenance.pixels_per_meter, provenance.requested_objects, provenance.seed).unwrap();
+		assert_eq!(provenance.seed, util::image_seed(provenance.run_seed.unwrap(), i));
+		regenerated.provenance.run_seed = provenance.run_seed;
+		assert_eq!(regenerated.provenance, provenance);
+		assert_eq!(regenerated.image, image::open(output.join(&file_name)).unwrap().to_rgba8());
+	}
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,101 @@
+// https://docs.ultralytics.com/datasets/detect/#ultralytics-yolo-format
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use crate::generator::error::GenerationError;
+use crate::generator::export::{create_label_file, label_path, LabelExporter};
+use crate::generator::placement::{GeneratedTarget, Placement};
+
+/// Writes YOLO labels, one .txt file per image with a line per object, and a classes.txt that maps the
+/// zero based class indices to names. Images without objects get an empty label file so that they are
+/// used as negatives.
+pub struct YoloExporter {
+	folder: PathBuf,
+	/// The category ids in the order of their YOLO class index
+	classes: Vec<u32>,
+}
+
+impl YoloExporter {
+	pub fn new<P: AsRef<Path>>(folder: P, names: HashMap<u32, String>) -> Result<Self, GenerationError> {
+		std::fs::create_dir_all(folder.as_ref())?;
+		
+		let mut classes = names.keys().copied().collect::<Vec<u32>>();
+		classes.sort();
+		
+		let class_names = classes.iter().map(|id| format!("{}\n", names[id])).collect::<String>();
+		std::fs::write(folder.as_ref().join("classes.txt"), class_names)?;
+		
+		Ok(Self {
+			folder: folder.as_ref().to_path_buf(),
+			classes,
+		})
+	}
+	
+	/// Produce a single label line for a placed object, with the center and size of its visible part
+	/// relative to the image size. Ignored distractors have no line.
+	pub fn label_line(&self, placement: &Placement, image_width: u32, image_height: u32) -> Option<String> {
+		if placement.ignore {
+			return None;
+		}
+		
+		let visible = placement.visible_bbox(image_width, image_height)?;
+		let class = self.classes.iter().position(|id| *id == placement.object_class)?;
+		let (width, height) = (image_width as f32, image_height as f32);
+		
+		Some(format!("{} {:.6} {:.6} {:.6} {:.6}",
+			class,
+			(visible.x as f32 + visible.width as f32 / 2.0) / width,
+			(visible.y as f32 + visible.height as f32 / 2.0) / height,
+			visible.width as f32 / width,
+			visible.height as f32 / height,
+		))
+	}
+}
+
+impl LabelExporter for YoloExporter {
+	fn export(&self, _image_id: u32, file_name: &str, target: &GeneratedTarget) -> Result<(), GenerationError> {
+		let mut labels = String::new();
+		
+		for placement in &target.placements {
+			if let Some(line) = self.label_line(placement, target.image.width(), target.image.height()) {
+				writeln!(labels, "{}", line).unwrap();
+			}
+		}
+		
+		create_label_file(&label_path(&self.folder, file_name, "txt"))?.write_all(labels.as_bytes())?;
+		
+		Ok(())
+	}
+}
+
+#[test]
+fn test_yolo_labels() {
+	use image::RgbaImage;
+	use crate::generator::coco::BoundingBox;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("yolo_labels");
+	let exporter = YoloExporter::new(dir.join("yolo"), HashMap::from([(1, "tire".to_string()), (7, "car".to_string())])).unwrap();
+	
+	let target = GeneratedTarget {
+		image: RgbaImage::new(100, 50),
+		placements: vec![
+			Placement::new(7, BoundingBox { x: 10, y: 10, width: 40, height: 20 }, 100, 50),
+			Placement::new(1, BoundingBox { x: 80, y: 40, width: 40, height: 20 }, 100, 50),
+		],
+		provenance: Default::default(),
+	};
+	exporter.export(4, "4.png", &target).unwrap();
+	exporter.export(5, "5.png", &GeneratedTarget { placements: vec![], ..target }).unwrap();
+	
+	assert_eq!(std::fs::read_to_string(dir.join("yolo").join("classes.txt")).unwrap(), "tire\ncar\n");
+	assert_eq!(std::fs::read_to_string(dir.join("yolo").join("4.txt")).unwrap().lines().collect::<Vec<&str>>(), vec![
+		"1 0.300000 0.400000 0.400000 0.400000",
+		"0 0.900000 0.900000 0.200000 0.200000",
+	]);
+	assert_eq!(std::fs::read_to_string(dir.join("yolo").join("5.txt")).unwrap(), "");
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::generator::error::GenerationError;
+use crate::objects::OBJECTS_FILE;
+
+/// The name of the manifest file in the output folder
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// A record of exactly which files make up a dataset and what it was generated from, so that a
+/// dataset can be proven to be the one a model was trained on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+	/// the version of the generator that wrote the dataset
+	pub version: String,
+	pub created: String,
+	/// the full config of the run
+	pub config: serde_json::Value,
+	/// the objects.json of the object library, absent for a folder-per-class library
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub objects_json: Option<FileEntry>,
+	/// the class.json files of a folder-per-class object library
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub class_files: Vec<FileEntry>,
+	/// every background the images were generated from
+	pub backgrounds: Vec<FileEntry>,
+	/// every sprite in the object library the objects were drawn from
+	pub sprites: Vec<FileEntry>,
+	/// every file in the output folder relative to it, along with the outputs that were written outside
+	/// of it, like the annotations file, by their absolute path
+	pub outputs: Vec<FileEntry>,
+}
+
+/// The hash and size of a single file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileEntry {
+	/// relative to the output folder for outputs inside of it and absolute for the rest, as given for inputs
+	pub path: String,
+	pub sha256: String,
+	pub size: u64,
+}
+
+impl FileEntry {
+	/// Hash the file at [path], recording it under [name]
+	pub fn hash<P: AsRef<Path>>(path: P, name: String) -> Result<Self, GenerationError> {
+		let file = File::open(path.as_ref())?;
+		let size = file.metadata()?.len();
+		let mut hasher = Sha256::new();
+		std::io::copy(&mut BufReader::new(file), &mut hasher)?;
+		
+		Ok(Self {
+			path: name,
+			sha256: format!("{:x}", hasher.finalize()),
+			size,
+		})
+	}
+}
+
+/// The result of checking a dataset against its manifest
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VerifyReport {
+	/// files in the manifest that do not exist
+	pub missing: Vec<String>,
+	/// files whose size or hash differs from the manifest
+	pub mismatched: Vec<String>,
+	/// files in the output folder that are not in the manifest
+	pub unlisted: Vec<String>,
+	/// the number of files that matched
+	pub verified: usize,
+}
+
+impl VerifyReport {
+	pub fn is_ok(&self) -> bool {
+		self.missing.is_empty() && self.mismatched.is_empty() && self.unlisted.is_empty()
+	}
+}
+
+impl Manifest {
+	/// Hash every file in [output] along with the inputs of the run. [extra_outputs] are outputs that may
+	/// live outside of the output folder, like the annotations file. [object_details] are the files that
+	/// describe the object library, see [crate::objects::ObjectManager::details_paths].
+	pub fn create(output: &Path, extra_outputs: &[PathBuf], object_details: &[PathBuf], backgrounds: &[PathBuf], sprites: &[PathBuf], config: serde_json::Value) -> Result<Self, GenerationError> {
+		let mut outputs = list_files(output)?;
+		
+		// compare absolute paths, ex. annotations.json is outside of out/ but out/../out/annotations.json is not
+		let absolute_output = output.canonicalize()?;
+		for path in extra_outputs {
+			let path = path.canonicalize()?;
+			if !path.starts_with(&absolute_output) {
+				outputs.push((path.clone(), path.display().to_string()));
+			}
+		}
+		
+		let (objects_json, class_files): (Vec<PathBuf>, Vec<PathBuf>) = object_details.iter().cloned()
+			.partition(|path| path.file_name().is_some_and(|name| name == OBJECTS_FILE));
+		
+		let datetime: DateTime<Local> = std::time::SystemTime::now().into();
+		
+		Ok(Self {
+			version: env!("CARGO_PKG_VERSION").to_string(),
+			created: datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+			config,
+			objects_json: hash_all(&inputs(&objects_json))?.pop(),
+			class_files: hash_all(&inputs(&class_files))?,
+			backgrounds: hash_all(&inputs(backgrounds))?,
+			sprites: hash_all(&inputs(sprites))?,
+			outputs: hash_all(&outputs)?,
+		})
+	}
+	
+	pub fn load<P: AsRef<Path>>(output: P) -> Result<Self, GenerationError> {
+		let file = fs::read_to_string(output.as_ref().join(MANIFEST_FILE))?;
+		
+		Ok(serde_json::from_str(&file)?)
+	}
+	
+	pub fn save<P: AsRef<Path>>(&self, output: P) -> Result<(), GenerationError> {
+		serde_json::to_writer_pretty(File::create(output.as_ref().join(MANIFEST_FILE))?, self)?;
+		
+		Ok(())
+	}
+}
+
+enum Check {
+	Verified,
+	Missing,
+	Mismatched,
+}
+
+/// Check the dataset in [output] against its manifest. Inputs are only checked if [check_inputs] is set,
+/// since they usually are not kept next to the dataset.
+pub fn verify<P: AsRef<Path>>(output: P, check_inputs: bool) -> Result<VerifyReport, GenerationError> {
+	let output = output.as_ref();
+	let manifest = Manifest::load(output)?;
+	
+	let mut expected = manifest.outputs.iter().map(|entry| {
+		let path = Path::new(&entry.path);
+		(if path.is_absolute() { path.to_path_buf() } else { output.join(path) }, entry)
+	}).collect::<Vec<_>>();
+	if check_inputs {
+		expected.extend(manifest.objects_json.iter().chain(&manifest.class_files).chain(&manifest.backgrounds).chain(&manifest.sprites).map(|entry| (PathBuf::from(&entry.path), entry)));
+	}
+	
+	let results = expected.par_iter().map(|(path, entry)| {
+		let check = if !path.is_file() {
+			Check::Missing
+		} else if FileEntry::hash(path, entry.path.clone()).is_ok_and(|actual| actual.size == entry.size && actual.sha256 == entry.sha256) {
+			Check::Verified
+		} else {
+			Check::Mismatched
+		};
+		
+		(entry.path.clone(), check)
+	}).collect::<Vec<(String, Check)>>();
+	
+	let mut report = VerifyReport::default();
+	for (path, check) in results {
+		match check {
+			Check::Verified => report.verified += 1,
+			Check::Missing => report.missing.push(path),
+			Check::Mismatched => report.mismatched.push(path),
+		}
+	}
+	
+	let listed = manifest.outputs.iter().map(|entry| entry.path.as_str()).collect::<BTreeSet<&str>>();
+	report.unlisted = list_files(output)?.into_iter().map(|(_, name)| name).filter(|name| !listed.contains(name.as_str())).collect();
+	
+	Ok(report)
+}
+
+/// Every file in [folder] and its sub folders except for the manifest, along with its path relative to
+/// [folder] separated by "/", sorted by that path
+fn list_files(folder: &Path) -> Result<Vec<(PathBuf, String)>, GenerationError> {
+	let mut files = vec![];
+	let mut folders = vec![folder.to_path_buf()];
+	
+	while let Some(current) = folders.pop() {
+		for entry in fs::read_dir(&current)? {
+			let path = entry?.path();
+			
+			if path.is_dir() {
+				folders.push(path);
+			} else if path != folder.join(MANIFEST_FILE) {
+				let name = path.strip_prefix(folder).unwrap().components()
+					.map(|c| c.as_os_str().to_string_lossy().to_string())
+					.collect::<Vec<String>>()
+					.join("/");
+				files.push((path, name));
+			}
+		}
+	}
+	
+	files.sort_by(|a, b| a.1.cmp(&b.1));
+	
+	Ok(files)
+}
+
+fn inputs(paths: &[PathBuf]) -> Vec<(PathBuf, String)> {
+	paths.iter().map(|path| (path.clone(), path.display().to_string())).collect()
+}
+
+fn hash_all(files: &[(PathBuf, String)]) -> Result<Vec<FileEntry>, GenerationError> {
+	files.par_iter().map(|(path, name)| FileEntry::hash(path, name.clone())).collect()
+}
+
+#[test]
+fn test_manifest_verify() {
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("manifest_verify");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.export_kitti = true;
+	tg.generate_targets(4, ..3, &output).unwrap();
+	tg.close();
+	
+	let manifest = Manifest::load(&output).unwrap();
+	assert!(manifest.outputs.iter().any(|entry| entry.path == "annotations.json"));
+	assert!(manifest.outputs.iter().any(|entry| entry.path == "kitti/0.txt"));
+	assert_eq!(manifest.sprites.len(), 2);
+	assert_eq!(manifest.config["export_kitti"], true);
+	assert!(manifest.config["seed"].is_u64(), "the random seed of the run is recorded");
+	
+	let report = verify(&output, true).unwrap();
+	assert!(report.is_ok(), "{:?}", report);
+	assert_eq!(report.verified, manifest.outputs.len() + manifest.backgrounds.len() + 3);
+	
+	fs::write(output.join("kitti").join("1.txt"), "tampered").unwrap();
+	fs::remove_file(output.join("2.png")).unwrap();
+	fs::write(output.join("extra.txt"), "").unwrap();
+	
+	let report = verify(&output, false).unwrap();
+	assert_eq!(report.mismatched, vec!["kitti/1.txt"]);
+	assert_eq!(report.missing, vec!["2.png"]);
+	assert_eq!(report.unlisted, vec!["extra.txt"]);
+	
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_manifest_outside_outputs() {
+	use crate::generator::util;
+	
+	let dir = util::test_dir("manifest_outside_outputs");
+	let output = dir.join("output");
+	fs::create_dir_all(&output).unwrap();
+	fs::write(output.join("0.png"), "image").unwrap();
+	fs::write(dir.join("annotations.json"), "{}").unwrap();
+	
+	// an annotations file next to the output folder is recorded by its absolute path, one inside it only once
+	let manifest = Manifest::create(&output, &[output.join("..").join("annotations.json"), output.join("0.png")], &[], &[], &[], serde_json::Value::Null).unwrap();
+	manifest.save(&output).unwrap();
+	let paths = manifest.outputs.iter().map(|entry| entry.path.clone()).collect::<Vec<String>>();
+	assert_eq!(paths, vec!["0.png".to_string(), dir.canonicalize().unwrap().join("annotations.json").display().to_string()]);
+	
+	let report = verify(&output, false).unwrap();
+	assert!(report.is_ok(), "{:?}", report);
+	assert_eq!(report.verified, 2);
+	
+	fs::remove_dir_all(dir).unwrap();
+}
@@ -1,17 +1,30 @@
 use crate::backgrounds::BackgroundLoader;
-use crate::generator::coco::{BoundingBox, CocoCategoryInfo, CocoGenerator};
-use crate::generator::config::TargetGeneratorConfig;
-use crate::objects::ObjectManager;
+use crate::generator::coco::{BoundingBox, CocoCategory, CocoCategoryInfo, CocoGenerator};
+use crate::generator::coco::journal::RunParameters;
+use crate::generator::config::{DistractorConfig, ObjectSampling, TargetGeneratorConfig};
+use crate::generator::difficulty::Difficulty;
+use crate::generator::export::cvat::{CvatExporter, CVAT_FILE};
+use crate::generator::export::kitti::KittiExporter;
+use crate::generator::export::labelme::LabelMeExporter;
+use crate::generator::export::provenance::ProvenanceExporter;
+use crate::generator::export::yolo::YoloExporter;
+use crate::generator::export::LabelExporter;
+use crate::generator::manifest::Manifest;
+use crate::objects::{recolor, ObjectManager, NADIR, PROCEDURAL_OBJECT_ID};
+use crate::standard_targets::StandardTargetSource;
 use error::GenerationError;
+use placement::{AugmentationRecord, GeneratedTarget, ImageProvenance, ObjectProvenance, Placement};
 use image::codecs::png::{CompressionType, PngEncoder};
 use image::imageops::FilterType;
-use image::{DynamicImage, ExtendedColorType, ImageEncoder, Rgba, RgbaImage};
-use log::{debug, trace, LevelFilter};
+use image::{DynamicImage, ExtendedColorType, ImageEncoder, Rgba};
+use log::{debug, trace, warn};
 use moka::sync::{Cache, CacheBuilder};
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::{thread_rng, Rng, SeedableRng};
 use rayon::iter::ParallelIterator;
 use rayon::iter::IntoParallelIterator;
-use simple_logger::SimpleLogger;
+use std::collections::{BTreeSet, HashSet};
 use std::ops::RangeTo;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
@@ -22,11 +35,23 @@ pub mod coco;
 pub mod error;
 pub mod util;
 pub mod config;
+pub mod placement;
+pub mod export;
+pub mod split;
+pub mod naming;
+pub mod difficulty;
+pub mod manifest;
+pub mod stats;
+pub mod clutter;
 
 /// The number of times to attempt placing an object in an image before giving up because the image
 /// is too crowded
 const COLLISION_ATTEMPTS: u32 = 15;
 
+/// The fraction of the standard pixels per meter that the hardest images of a curriculum run use,
+/// making their objects this much smaller than in the easiest images
+const CURRICULUM_HARDEST_SCALE: f32 = 0.5;
+
 pub struct TargetGenerator {
 	backgrounds_path: PathBuf,
 	pub object_manager: ObjectManager,
@@ -34,6 +59,11 @@ pub struct TargetGenerator {
 	coco_generator: Arc<Mutex<CocoGenerator>>,
 	pub config: TargetGeneratorConfig,
 	resized_cache: Cache<String, DynamicImage>,
+	standard_targets: StandardTargetSource,
+	/// the library of objects of non-interest, see [TargetGenerator::load_distractors]
+	distractors: Option<ObjectManager>,
+	/// the folder that the last call to [TargetGenerator::generate_targets] wrote to
+	output_path: Option<PathBuf>,
 }
 
 impl TargetGenerator {
@@ -58,57 +88,175 @@ impl TargetGenerator {
 			coco_generator: Arc::new(Mutex::new(CocoGenerator::new(annotations_path, categories))),
 			config,
 			resized_cache,
+			standard_targets: StandardTargetSource::new(),
+			distractors: None,
+			output_path: None,
 		})
 	}
+	
+	/// Load a library of objects of non-interest, ex. tents, trees and people, laid out like the objects
+	/// library. Distractors are scaled and placed like the objects but are left out of the annotations
+	/// unless [DistractorConfig::annotate] is set. Enables [TargetGeneratorConfig::distractors] if unset.
+	/// If a distractor class has the id of an object class, ex. the folders of a folder-per-class library
+	/// are numbered from 0, the distractor classes are renumbered to follow the largest object class.
+	pub fn load_distractors<P: AsRef<Path>>(&mut self, distractors_path: P) -> Result<(), GenerationError> {
+		let mut distractors = ObjectManager::new(distractors_path);
+		distractors.load_objects()?;
+		
+		let classes = self.object_manager.categories().iter().map(|c| c.id()).collect::<HashSet<u32>>();
+		if distractors.categories().iter().any(|c| classes.contains(&c.id())) {
+			let offset = classes.iter().max().map_or(0, |max| max + 1);
+			warn!("Distractor classes share ids with the object classes, numbering them from {} instead", offset);
+			distractors.offset_classes(offset);
+		}
+		
+		self.config.distractors.get_or_insert_with(DistractorConfig::default);
+		self.distractors = Some(distractors);
+		
+		Ok(())
+	}
 
-	pub fn generate_target(&self, pixels_per_meter: f32, number_of_objects: u16) -> Result<RgbaImage, GenerationError> {
+	/// Generate a single target image. The image is not registered with the COCO generator, instead
+	/// the placement records are returned so that the caller can annotate it once it has been written.
+	/// Every random choice is drawn from an RNG seeded with [seed], so the same seed and inputs
+	/// reproduce the same image. Zero objects produce a negative that only shows the background.
+	pub fn generate_target(&self, pixels_per_meter: f32, number_of_objects: u16, seed: u64) -> Result<GeneratedTarget, GenerationError> {
+		self.generate(pixels_per_meter, number_of_objects, None, None, seed)
+	}
+	
+	/// Generate a single target image at a level of a curriculum run, from 0.0 (easiest) to 1.0 (hardest).
+	/// The level sets the blur and noise strength instead of sampling it, see [TargetGenerator::generate_target]
+	pub fn generate_target_at_level(&self, pixels_per_meter: f32, number_of_objects: u16, level: f32, seed: u64) -> Result<GeneratedTarget, GenerationError> {
+		self.generate(pixels_per_meter, number_of_objects, None, Some(level), seed)
+	}
+	
+	/// Generate a single target image with one object of each of the given classes, see [TargetGenerator::generate_target]
+	pub fn generate_target_with_classes(&self, pixels_per_meter: f32, classes: &[u32], seed: u64) -> Result<GeneratedTarget, GenerationError> {
+		self.generate(pixels_per_meter, classes.len() as u16, Some(classes), None, seed)
+	}
+	
+	fn generate(&self, pixels_per_meter: f32, number_of_objects: u16, classes: Option<&[u32]>, level: Option<f32>, seed: u64) -> Result<GeneratedTarget, GenerationError> {
 		trace!("Beginning to generate a target...");
 		
-		if number_of_objects == 0 {
-			return Err(GenerationError::NoObjects);
-		}
+		let mut rng = StdRng::seed_from_u64(seed);
 
-		let background = self.background_loader.random().unwrap();
+		let background = self.background_loader.random(&mut rng).unwrap();
 		let mut image = background.image.clone();
 		let (w, h) = (image.width(), image.height());
-		let set = self.object_manager.generate_set(number_of_objects as u32, &self.config)?;
+		
+		// the ground clutter is part of the background, the objects are pasted on top of it
+		let clutter = self.config.clutter.map(|clutter| clutter::draw_clutter(&mut image, &clutter, pixels_per_meter, &mut rng));
+		
+		let set = if number_of_objects == 0 {
+			vec![] // a negative that only shows the background
+		} else if let Some(classes) = classes {
+			self.object_manager.generate_set_for_classes(classes, &self.config, &mut rng)?
+		} else {
+			self.object_manager.generate_set(number_of_objects as u32, &self.config, &mut rng)?
+		};
+		let standard_targets = match &self.config.standard_targets {
+			Some(config) if number_of_objects > 0 => self.standard_targets.generate_set(config, pixels_per_meter, &mut rng),
+			_ => vec![],
+		};
+		let distractors = match (&self.config.distractors, &self.distractors) {
+			(Some(config), Some(distractors)) => {
+				// distractors only fill in the scene, so a small library is drawn from with replacement
+				let count = rng.gen_range(config.min_count..=config.max_count.max(config.min_count));
+				distractors.generate_set_with_replacement(count, &mut rng)
+			}
+			_ => vec![],
+		};
 		let mut placed_objects = vec![];
+		let mut placements = vec![];
+		let mut provenance = ImageProvenance {
+			seed,
+			background: background.filename.clone(),
+			pixels_per_meter,
+			requested_objects: number_of_objects,
+			requested_classes: classes.map(|c| c.to_vec()),
+			curriculum_level: level,
+			augmentations: clutter.into_iter().collect(),
+			..Default::default()
+		};
+		if self.config.min_camera_elevation < NADIR {
+			provenance.camera_elevation = Some(rng.gen_range(self.config.min_camera_elevation..=NADIR));
+		}
+		let camera_elevation = provenance.camera_elevation.unwrap_or(NADIR);
 		
-		// add background image to coco here
-		let background_id = self.coco_generator.lock().unwrap().add_image(w, h, background.filename.clone(), background.date_captured.clone());
+		// distractors go down first so that they never hide the objects
+		let objects = distractors.into_iter().map(|obj| (obj, true))
+			.chain(set.into_iter().chain(&standard_targets).map(|obj| (obj, false)));
 		
-		for obj in set {
-			let clone = &obj.dynamic_image.clone();
-			let (obj_w, obj_h) = (obj.dynamic_image.width(), obj.dynamic_image.height());
-			let (x, y) = if let Ok((x, y)) = self.generate_new_location_no_collision((w, h), (obj_w, obj_h), &placed_objects) {
+		for (obj, distractor) in objects {
+			// objects with several views show the one closest to the sampled heading, the rest of the heading is rotated
+			let heading = (!obj.views.is_empty() && self.config.do_random_rotation).then(|| rng.gen_range(0..360));
+			let view = obj.closest_view(heading.unwrap_or(0) as f32, camera_elevation);
+			let (sprite, ground_width, view_yaw) = match view {
+				Some((_, view)) => (&view.dynamic_image, view.object_width_meters, view.yaw.round() as i32),
+				None => (&obj.dynamic_image, obj.object_width_meters, 0),
+			};
+			
+			let clone = &sprite.clone();
+			let (obj_w, obj_h) = (sprite.width(), sprite.height());
+			let (x, y) = if let Ok((x, y)) = self.generate_new_location_no_collision((w, h), (obj_w, obj_h), &placed_objects, &mut rng) {
 				(x, y)
 			} else {
 				continue; // TODO: maybe it should break instead? might result in occasional empty images if so
 			};
 			trace!("Placing object at {}, {}", x, y);
 			
-			let (obj_w, obj_h) = util::new_sizes(obj_w, obj_h, pixels_per_meter, obj.object_width_meters)?;
+			let (obj_w, obj_h) = util::new_sizes(obj_w, obj_h, pixels_per_meter, ground_width)?;
 			trace!("Resizing object to {}x{}", obj_w, obj_h);
+			let scale = obj_w as f32 / sprite.width() as f32;
 			
+			// distractor ids are counted separately from the object ids
+			let key = format!("{}{}x{}_{}_{}", if distractor { "distractor_" } else { "" }, obj_w, obj_h, obj.id, view.map(|(i, _)| i).unwrap_or(0));
 			// overlay respects transparent pixels unlike copy_from
-			let resized = if let Some(resized) = self.resized_cache.get(&format!("{}x{}_{}", obj_w, obj_h, obj.object_class)) {
+			let resized = if obj.id == PROCEDURAL_OBJECT_ID {
+				clone.resize(obj_w, obj_h, FilterType::Gaussian) // drawn for this image only, not worth caching
+			} else if let Some(resized) = self.resized_cache.get(&key) {
 				resized.clone()
 			} else {
 				let resized = clone.resize(obj_w, obj_h, FilterType::Gaussian);
-				self.resized_cache.insert(format!("{}x{}_{}", obj_w, obj_h, obj.object_class), resized.clone());
+				self.resized_cache.insert(key, resized.clone());
 				resized
 			};
-
+			
+			let mut augmentations = vec![];
+			let mut attributes = obj.attributes.clone();
+			
+			if let Some((_, view)) = view {
+				augmentations.push(AugmentationRecord::new("select_view")
+					.with("sprite", view.file_name.as_str())
+					.with("yaw", view.yaw)
+					.with("elevation", view.elevation)
+					.with("heading", heading.unwrap_or(0)));
+			}
+			
+			// swap the dominant color of the sprite for one of the colors of its class
+			let sampled = obj.recolor.as_ref().and_then(|options| options.sample(&mut rng).map(|color| (options, color)));
+			let resized = match sampled.and_then(|(options, (name, color))| Some((name, recolor::recolor(&resized.to_rgba8(), color, options.hue_tolerance)?))) {
+				Some((name, recolored)) => {
+					augmentations.push(AugmentationRecord::new("recolor").with("color", name));
+					attributes.insert("color".to_string(), name.into());
+					DynamicImage::ImageRgba8(recolored)
+				}
+				None => resized,
+			};
+			
 			// random rotations in multiples of 90 degrees
-			let resized = if self.config.do_random_rotation {
-				let angle = thread_rng().gen_range(0..360); // random rotation including upside down
-				let rotated = util::rotate_90s(&resized, angle);
-				rotated
+			let (resized, rotation) = if self.config.do_random_rotation {
+				// random rotation including upside down, only what the view does not already face for multi-view objects
+				let angle = heading.map(|heading| util::residual_rotation(heading, view_yaw)).unwrap_or_else(|| rng.gen_range(0..360));
+				let rotation = (angle / 90 * 90) as u32;
+				augmentations.push(AugmentationRecord::new("rotate_90s").with("sampled_angle", angle).with("rotation", rotation));
+				(util::rotate_90s(&resized, angle), rotation)
 			} else {
-				resized // return as is if random rotation is not performed
+				(resized, 0) // return as is if random rotation is not performed
 			};
 			
 			let (obj_w, obj_h) = (resized.width(), resized.height());
+			let contrast = difficulty::local_contrast(&image, &resized.to_rgba8(), x, y);
 
 			image::imageops::overlay(&mut image, &resized, x as i64, y as i64);
 			
@@ -127,45 +275,263 @@ impl TargetGenerator {
 				height: obj_h,
 			};
 			
-			// add annotation to coco here
-			self.coco_generator.lock().unwrap().add_annotation(background_id, obj.object_class, 0, vec![], (obj_w * obj_h) as f64, bbox);
+			let mut placement = Placement::new(obj.object_class, bbox, w, h);
+			placement.attributes = attributes.clone();
+			placement.ignore = distractor;
+			placements.push(placement);
+			provenance.objects.push(ObjectProvenance {
+				sprite: view.map(|(_, view)| &view.file_name).unwrap_or(&obj.file_name).clone(),
+				object_id: obj.id,
+				object_class: obj.object_class,
+				x,
+				y,
+				scale,
+				rotation,
+				contrast,
+				augmentations,
+				attributes,
+				distractor,
+				placement: None,
+			});
 			
 			placed_objects.push(bbox);
 		}
+		
+		placement::compute_occlusion(&mut placements);
+		let annotate_distractors = self.config.distractors.is_some_and(|config| config.annotate);
+		if !annotate_distractors {
+			placements.retain(|placement| !placement.ignore);
+		}
+		for (index, object) in provenance.objects.iter_mut().filter(|object| annotate_distractors || !object.distractor).enumerate() {
+			object.placement = Some(index);
+		}
+		
+		// degrade the whole image, either sampled or set by the curriculum level
+		let mut strength = |max: f32| match level {
+			_ if max <= 0.0 => 0.0,
+			Some(level) => max * level.clamp(0.0, 1.0),
+			None => rng.gen_range(0.0..=max),
+		};
+		let (blur_sigma, noise_sigma) = (strength(self.config.max_blur_sigma), strength(self.config.max_noise_sigma));
+		
+		if blur_sigma > 0.0 {
+			image = imageproc::filter::gaussian_blur_f32(&image, blur_sigma);
+			provenance.augmentations.push(AugmentationRecord::new("gaussian_blur").with("sigma", blur_sigma));
+		}
+		
+		if noise_sigma > 0.0 {
+			difficulty::add_gaussian_noise(&mut image, noise_sigma, &mut rng);
+			provenance.augmentations.push(AugmentationRecord::new("gaussian_noise").with("sigma", noise_sigma));
+		}
+		
+		provenance.difficulty = Difficulty::compute(&placements, &provenance.objects, w, h, blur_sigma, noise_sigma);
 
-		Ok(image)
+		Ok(GeneratedTarget {
+			image,
+			placements,
+			provenance,
+		})
 	}
 
 	pub fn generate_targets<A: AsRef<Path> + Sync>(&mut self, amount: u32, range_to: RangeTo<u32>, path: A) -> Result<(), GenerationError> {
 		let start = Instant::now(); // start timer
 		debug!("Generating {} targets...", amount);
 
+		self.config.naming.validate()?;
+		if self.config.resume && self.config.export_cvat {
+			return Err(GenerationError::GenericError("Resuming is not supported with the CVAT export, it would only hold the images generated after resuming".to_string()));
+		}
+		if let Some(standard_targets) = &self.config.standard_targets && let Some(category) = self.object_manager.categories().into_iter().find(|c| c.id() == standard_targets.category_id) {
+			return Err(GenerationError::GenericError(format!("The standard targets use category id {}, which is already the id of the object class {}", category.id(), category.name())));
+		}
+		if let (Some(DistractorConfig { annotate: true, .. }), Some(distractors)) = (&self.config.distractors, &self.distractors) {
+			let classes = self.target_categories().iter().map(|c| c.id()).collect::<HashSet<u32>>();
+			if let Some(category) = distractors.categories().into_iter().find(|c| classes.contains(&c.id())) {
+				return Err(GenerationError::GenericError(format!("The distractor class {} uses category id {}, which is already the id of another class", category.name(), category.id())));
+			}
+		}
 		let threadpool = rayon::ThreadPoolBuilder::new().num_threads(self.config.worker_threads as usize).build().unwrap();
+		let exporters = self.exporters(path.as_ref())?;
+		if let Some(standard_targets) = &self.config.standard_targets {
+			self.coco_generator.lock().unwrap().add_category(standard_targets.category());
+		}
+		if let (Some(DistractorConfig { annotate: true, .. }), Some(distractors)) = (&self.config.distractors, &self.distractors) {
+			for category in distractors.categories() {
+				self.coco_generator.lock().unwrap().add_category(category);
+			}
+		}
+		self.output_path = Some(path.as_ref().to_path_buf());
+		
+		// a resumed run continues with the seed and parameters of the interrupted run so that it plans the same images
+		let run = RunParameters {
+			amount,
+			max_objects: range_to.end,
+			negative_ratio: self.config.negative_ratio,
+			object_sampling: self.config.object_sampling,
+			curriculum: self.config.curriculum,
+		};
+		let (journaled_seed, journaled_run) = if self.config.resume {
+			self.coco_generator.lock().unwrap().journal_run()?
+		} else {
+			(None, None)
+		};
+		if let Some(journaled) = journaled_run && journaled != run {
+			return Err(GenerationError::GenericError(format!("Cannot resume with {:?}, the interrupted run was planned with {:?}", run, journaled)));
+		}
+		let run_seed = match (self.config.seed, journaled_seed) {
+			(Some(seed), Some(journaled)) if seed != journaled => {
+				return Err(GenerationError::GenericError(format!("Cannot resume with seed {}, the interrupted run used seed {}", seed, journaled)));
+			}
+			(seed, journaled) => seed.or(journaled).unwrap_or_else(|| thread_rng().r#gen()),
+		};
+		debug!("Using seed {}", run_seed);
+		self.coco_generator.lock().unwrap().set_run(run_seed, run);
+		
+		let completed = if self.config.resume {
+			self.coco_generator.lock().unwrap().resume(path.as_ref())?
+		} else {
+			HashSet::new()
+		};
+		let remaining = (0..amount).filter(|i| !completed.contains(i)).collect::<Vec<u32>>();
+		
+		// plan every image up front so that the classes of a balanced run can be spread over the whole run
+		let mut run_rng = StdRng::seed_from_u64(run_seed);
+		let negatives = if self.config.negative_ratio > 0.0 {
+			let count = (amount as f32 * self.config.negative_ratio.min(1.0)).round() as usize;
+			(0..amount).choose_multiple(&mut run_rng, count).into_iter().collect::<HashSet<u32>>()
+		} else {
+			HashSet::new()
+		};
+		debug!("Generating {} negatives without objects", negatives.len());
+		let plans = (0..amount).map(|i| {
+			let seed = util::image_seed(run_seed, i);
+			let count = if negatives.contains(&i) { 0 } else { StdRng::seed_from_u64(util::count_seed(run_seed, i)).gen_range(1..range_to.end) as u16 };
+			(seed, count)
+		}).collect::<Vec<(u64, u16)>>();
+		let schedule = if self.config.object_sampling == ObjectSampling::BalancedRun {
+			let total = plans.iter().map(|(_, count)| *count as usize).sum();
+			Some(self.object_manager.balanced_schedule(total, &mut run_rng))
+		} else {
+			None
+		};
+		let offsets = plans.iter().scan(0, |offset, (_, count)| {
+			let start = *offset;
+			*offset += *count as usize;
+			Some(start)
+		}).collect::<Vec<usize>>();
+		
+		let generating = remaining.len();
+		if !completed.is_empty() {
+			debug!("Resuming, {} targets are already complete", completed.len());
+		}
 		
 		threadpool.install(|| {
-			(0..amount).into_par_iter().for_each(|i| {
-				let b = self.generate_target(STANDARD_PPM, thread_rng().gen_range(1..range_to.end) as u16).unwrap();
-				let path = path.as_ref().join(format!("{}.png", i));
+			remaining.into_par_iter().for_each(|i| {
+				let (seed, count) = plans[i as usize];
+				let level = self.config.curriculum.then(|| i as f32 / (amount - 1).max(1) as f32);
+				let ppm = level.map(|level| STANDARD_PPM * (1.0 - (1.0 - CURRICULUM_HARDEST_SCALE) * level)).unwrap_or(STANDARD_PPM);
+				let classes = schedule.as_ref().map(|schedule| {
+					let offset = offsets[i as usize];
+					&schedule[offset..offset + count as usize]
+				});
+				let mut target = self.generate(ppm, count, classes, level, seed).unwrap();
+				target.provenance.run_seed = Some(run_seed);
+				let b = &target.image;
+				let file_name = self.config.naming.file_name(i, run_seed, &target.provenance.background).unwrap();
+				let path = path.as_ref().join(&file_name);
+				
+				if let Some(parent) = path.parent() {
+					std::fs::create_dir_all(parent).unwrap();
+				}
 
 				if self.config.compress {
 					let mut file = std::fs::File::create(path.clone()).unwrap();
 					let encoder = PngEncoder::new_with_quality(&mut file, CompressionType::Best, image::codecs::png::FilterType::Avg);
-					encoder.write_image(&b, b.width(), b.height(), ExtendedColorType::Rgba8).unwrap();
+					encoder.write_image(b, b.width(), b.height(), ExtendedColorType::Rgba8).unwrap();
 				} else {
 					b.save(path.clone()).unwrap();
 				}
+				
+				// register the image only once it has been written, using the index as the image id
+				let mut coco = self.coco_generator.lock().unwrap();
+				let image_id = coco.add_image(i, b.width(), b.height(), file_name.clone(), target.provenance.background.clone(), Some(target.provenance.difficulty.clone()));
+				for placement in &target.placements {
+					coco.add_placement(image_id, placement);
+				}
+				drop(coco);
+				
+				for exporter in &exporters {
+					exporter.export(image_id, &file_name, &target).unwrap();
+				}
+				
+				// journal last, a journaled image is one that has all of its outputs written
+				self.coco_generator.lock().unwrap().journal_image(image_id).unwrap();
 
 				debug!("Saved generated target to {}", path.display().to_string().replace("\\", "/"));
 			});
 		});
+		
+		for exporter in &exporters {
+			exporter.finish()?;
+		}
 
-
-		debug!("Generation completed, generated {} in average {}ms", amount, start.elapsed().as_millis() / amount as u128);
+		debug!("Generation completed, generated {} in average {}ms", generating, start.elapsed().as_millis() / generating.max(1) as u128);
 
 		Ok(())
 	}
 	
-	pub fn generate_new_location_no_collision(&self, bg_dimensions: (u32, u32), obj_dimensions: (u32, u32), placed_objects: &Vec<BoundingBox>) -> Result<(u32, u32), GenerationError> {
+	/// The categories of the sprite objects along with the standard targets and annotated distractors if they are enabled
+	pub fn categories(&self) -> Vec<CocoCategory> {
+		let mut categories = self.target_categories();
+		
+		if let (Some(DistractorConfig { annotate: true, .. }), Some(distractors)) = (&self.config.distractors, &self.distractors) {
+			categories.extend(distractors.categories());
+		}
+		
+		categories
+	}
+	
+	/// The categories of the sprite objects along with the standard targets if they are enabled
+	fn target_categories(&self) -> Vec<CocoCategory> {
+		let mut categories = self.object_manager.categories();
+		
+		if let Some(standard_targets) = &self.config.standard_targets {
+			categories.push(standard_targets.category());
+		}
+		
+		categories
+	}
+	
+	/// Create the additional label exporters that are enabled in the config
+	fn exporters(&self, path: &Path) -> Result<Vec<Box<dyn LabelExporter>>, GenerationError> {
+		let names = export::category_names(&self.categories());
+		let mut exporters: Vec<Box<dyn LabelExporter>> = vec![];
+		
+		if self.config.write_provenance {
+			exporters.push(Box::new(ProvenanceExporter::new(path.join("provenance"))?));
+		}
+		
+		if self.config.export_kitti {
+			exporters.push(Box::new(KittiExporter::new(path.join("kitti"), names.clone())?));
+		}
+		
+		if self.config.export_cvat {
+			exporters.push(Box::new(CvatExporter::new(path.join(CVAT_FILE), names.clone())));
+		}
+		
+		if self.config.export_labelme {
+			exporters.push(Box::new(LabelMeExporter::new(path.join("labelme"), names.clone())?));
+		}
+		
+		if self.config.export_yolo {
+			// YOLO has no way to flag a box as ignored, so distractors are left out of it entirely
+			exporters.push(Box::new(YoloExporter::new(path.join("yolo"), export::category_names(&self.target_categories()))?));
+		}
+		
+		Ok(exporters)
+	}
+	
+	pub fn generate_new_location_no_collision<R: Rng + ?Sized>(&self, bg_dimensions: (u32, u32), obj_dimensions: (u32, u32), placed_objects: &[BoundingBox], rng: &mut R) -> Result<(u32, u32), GenerationError> {
 		let mut i = 0;
 		
 		loop {
@@ -173,8 +539,8 @@ impl TargetGenerator {
 				return Err(GenerationError::TooManyCollisions)
 			}
 			
-			let x = thread_rng().gen_range(0..bg_dimensions.0);
-			let y = thread_rng().gen_range(0..bg_dimensions.1);
+			let x = rng.gen_range(0..bg_dimensions.0);
+			let y = rng.gen_range(0..bg_dimensions.1);
 
 			if self.config.permit_collisions {
 				return Ok((x, y));
@@ -195,23 +561,60 @@ impl TargetGenerator {
 		}
 	}
 	
-	/// Finish writing the annotations.json file
+	/// Finish writing the annotations.json file, assembling it from the journal written during generation.
+	/// If splits are configured the generated images are then divided into their splits. Last, a
+	/// manifest.json with the hash of every output and input file is written into the output folder.
 	pub fn close(&self) {
-		self.coco_generator.lock().unwrap().save();
+		let mut coco = self.coco_generator.lock().unwrap();
+		coco.finalize().unwrap();
+		
+		if let (Some(splits), Some(output)) = (&self.config.splits, &self.output_path) {
+			let annotations_path = coco.file_path().to_path_buf();
+			split::split_dataset(output, &annotations_path, &mut coco.file, splits).unwrap();
+			coco.save(); // file names now include the split folder
+		}
+		
+		if let Some(output) = &self.output_path {
+			self.write_manifest(output, &coco).unwrap();
+		}
+	}
+	
+	/// Write the manifest once every output is in its final place
+	fn write_manifest(&self, output: &Path, coco: &CocoGenerator) -> Result<(), GenerationError> {
+		let backgrounds = coco.file.images().iter()
+			.filter_map(|image| image.background())
+			.collect::<BTreeSet<&str>>()
+			.into_iter()
+			.map(PathBuf::from)
+			.collect::<Vec<PathBuf>>();
+		let mut sprites = self.object_manager.sprite_paths();
+		sprites.extend(self.distractors.iter().flat_map(|distractors| distractors.sprite_paths()));
+		// record the seed that was actually used, which was drawn at random if none was set
+		let mut config = serde_json::to_value(&self.config)?;
+		config["seed"] = coco.seed().into();
+		
+		let manifest = Manifest::create(output, &[coco.file_path().to_path_buf()], &self.object_manager.details_paths()?, &backgrounds, &sprites, config)?;
+		manifest.save(output)?;
+		debug!("Wrote manifest of {} files", manifest.outputs.len());
+		
+		Ok(())
 	}
 }
 
 #[test]
 #[ignore]
 pub fn test_generate_target() {
+	use log::LevelFilter;
+	use simple_logger::SimpleLogger;
+	
 	SimpleLogger::new().with_level(LevelFilter::Debug).init().unwrap();
 
 	let mut tg = TargetGenerator::new("backgrounds", "objects", "output/annotations.json").unwrap();
 	tg.config.permit_duplicates = true;
 	tg.config.permit_collisions = false;
-	let b = tg.generate_target(STANDARD_PPM, 5).unwrap();
+	let b = tg.generate_target(STANDARD_PPM, 5, thread_rng().r#gen()).unwrap().image;
 
-	b.save("output_1.png".to_string()).unwrap();
+	b.save("output_1.png").unwrap();
 	debug!("Saved generated target to output_1.png");
 	
 	tg.close();
@@ -220,6 +623,9 @@ pub fn test_generate_target() {
 #[test]
 #[ignore]
 pub fn test_generate_targets() {
+	use log::LevelFilter;
+	use simple_logger::SimpleLogger;
+	
 	SimpleLogger::new().with_level(LevelFilter::Debug).init().unwrap();
 
 	let mut tg = TargetGenerator::new("../backgrounds", "../objects", "../output/annotations.json").unwrap();
@@ -230,4 +636,205 @@ pub fn test_generate_targets() {
 	tg.generate_targets(10, ..6u32, "../output").unwrap();
 	
 	tg.close();
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_seed_independent_of_threads() {
+	use std::collections::HashMap;
+	use crate::generator::coco::CocoFormatFile;
+	use crate::generator::export::provenance::ProvenanceExporter;
+	
+	let dir = util::test_dir("seed_independent_of_threads");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	
+	let mut outputs = vec![];
+	for threads in [1, 8] {
+		let output = dir.join(format!("output_{}", threads));
+		std::fs::create_dir_all(&output).unwrap();
+		
+		let mut tg = TargetGenerator::new(&backgrounds, &objects, &output.join("annotations.json")).unwrap();
+		tg.config.permit_duplicates = true;
+		tg.config.export_kitti = true;
+		tg.config.write_provenance = true;
+		tg.config.worker_threads = threads;
+		tg.config.seed = Some(1234);
+		tg.generate_targets(12, ..4, &output).unwrap();
+		tg.close();
+		
+		outputs.push(output);
+	}
+	
+	for i in 0..12 {
+		for file in [format!("{}.png", i), format!("kitti/{}.txt", i), format!("provenance/{}.json", i)] {
+			assert_eq!(std::fs::read(outputs[0].join(&file)).unwrap(), std::fs::read(outputs[1].join(&file)).unwrap(), "{} differs", file);
+		}
+	}
+	
+	// the count is drawn from its own stream, not from the seed that picks the background
+	let mut counts = HashMap::<String, HashSet<u16>>::new();
+	for i in 0..12 {
+		assert_ne!(util::count_seed(1234, i), util::image_seed(1234, i));
+		let provenance = ProvenanceExporter::load(outputs[0].join("provenance"), &format!("{}.png", i)).unwrap();
+		assert_eq!(provenance.requested_objects, StdRng::seed_from_u64(util::count_seed(1234, i)).gen_range(1..4u32) as u16);
+		counts.entry(provenance.background).or_default().insert(provenance.requested_objects);
+	}
+	assert!(counts.values().any(|counts| counts.len() > 1), "{:?}", counts);
+	
+	let (a, b) = (CocoFormatFile::load(outputs[0].join("annotations.json")).unwrap(), CocoFormatFile::load(outputs[1].join("annotations.json")).unwrap());
+	assert_eq!(a.annotations(), b.annotations());
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_count_independent_of_background() {
+	use rand::seq::SliceRandom;
+	
+	// the background is the first choice of an image, pick it the way the background loader does
+	let backgrounds = (0..16).collect::<Vec<usize>>();
+	let mut counts = [[0u32; 5]; 16];
+	for i in 0..16000 {
+		let background = *backgrounds.choose(&mut StdRng::seed_from_u64(util::image_seed(1234, i))).unwrap();
+		let count = StdRng::seed_from_u64(util::count_seed(1234, i)).gen_range(1..6);
+		counts[background][count - 1] += 1;
+	}
+	
+	// about 200 images of each count on each background
+	for (background, counts) in counts.iter().enumerate() {
+		assert!(counts.iter().all(|c| (100..300).contains(c)), "background {} has counts {:?}", background, counts);
+	}
+}
+
+#[test]
+fn test_negative_images() {
+	use crate::generator::coco::CocoFormatFile;
+	
+	let dir = util::test_dir("negative_images");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(&backgrounds, &objects, &output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.export_yolo = true;
+	tg.config.negative_ratio = 0.25;
+	tg.generate_targets(20, ..4, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	assert_eq!(file.images().len(), 20);
+	
+	let negatives = file.images().iter().filter(|image| !file.annotations().iter().any(|a| a.image_id() == image.id())).collect::<Vec<_>>();
+	assert_eq!(negatives.len(), 5);
+	
+	for image in negatives {
+		assert_eq!(std::fs::read_to_string(output.join("yolo").join(format!("{}.txt", image.id()))).unwrap(), "");
+	}
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_distractors() {
+	use crate::generator::coco::CocoFormatFile;
+	
+	let dir = util::test_dir("distractors");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let distractors = dir.join("distractors");
+	std::fs::create_dir_all(distractors.join("tree")).unwrap();
+	image::RgbaImage::from_pixel(30, 30, Rgba([20, 90, 20, 255])).save(distractors.join("tree/tree_1.png")).unwrap();
+	std::fs::write(distractors.join("tree/class.json"), r#"{ "ground_width": 2.0 }"#).unwrap();
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(&backgrounds, &objects, &output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.permit_collisions = true;
+	tg.load_distractors(&distractors).unwrap();
+	tg.config.distractors = Some(DistractorConfig { min_count: 2, max_count: 2, annotate: false });
+	
+	// placed like the objects but left out of the annotations
+	let target = tg.generate_target(STANDARD_PPM, 2, 7).unwrap();
+	assert_eq!(target.provenance.objects.iter().filter(|o| o.distractor).count(), 2);
+	assert!(target.provenance.objects.iter().all(|o| o.placement.is_none() == o.distractor));
+	assert_eq!(target.placements.len(), 2);
+	for object in &target.provenance.objects {
+		if let Some(index) = object.placement {
+			assert_eq!(target.placements[index].object_class, object.object_class);
+		}
+	}
+	assert!(target.placements.iter().all(|p| !p.ignore));
+	
+	tg.config.distractors = Some(DistractorConfig { min_count: 2, max_count: 2, annotate: true });
+	tg.config.export_kitti = true;
+	tg.generate_targets(3, ..3, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	assert!(file.categories().iter().any(|c| c.id() == 0));
+	let ignored = file.annotations().iter().filter(|a| a.ignore()).collect::<Vec<_>>();
+	assert_eq!(ignored.len(), 6);
+	assert!(ignored.iter().all(|a| a.category_id() == 0));
+	assert!(std::fs::read_to_string(output.join("kitti/0.txt")).unwrap().contains("DontCare"));
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_distractor_class_clash() {
+	use crate::standard_targets::StandardTargetConfig;
+	
+	let dir = util::test_dir("distractor_class_clash");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let distractors = dir.join("distractors");
+	for class in ["person", "tree"] {
+		std::fs::create_dir_all(distractors.join(class)).unwrap();
+		image::RgbaImage::from_pixel(30, 30, Rgba([20, 90, 20, 255])).save(distractors.join(class).join(format!("{}_1.png", class))).unwrap();
+	}
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	// the folders are numbered 0 and 1, tree would be annotated as the tire object class
+	let mut tg = TargetGenerator::new(&backgrounds, &objects, &output.join("annotations.json")).unwrap();
+	tg.load_distractors(&distractors).unwrap();
+	tg.config.distractors = Some(DistractorConfig { annotate: true, ..Default::default() });
+	let categories = tg.categories().into_iter().map(|c| (c.id(), c.name().to_string())).collect::<Vec<_>>();
+	assert_eq!(categories, vec![(1, "tire".to_string()), (7, "car".to_string()), (8, "person".to_string()), (9, "tree".to_string())]);
+	
+	// the standard targets are not renumbered, so they must not take the id of an annotated distractor
+	tg.config.standard_targets = Some(StandardTargetConfig { category_id: 8, ..Default::default() });
+	let error = tg.generate_targets(2, ..2, &output).unwrap_err();
+	assert!(matches!(error, GenerationError::GenericError(message) if message.contains("person")));
+	tg.close();
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_distractors_default_config() {
+	let dir = util::test_dir("distractors_default_config");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let distractors = dir.join("distractors");
+	std::fs::create_dir_all(distractors.join("tree")).unwrap();
+	image::RgbaImage::from_pixel(30, 30, Rgba([20, 90, 20, 255])).save(distractors.join("tree/tree_1.png")).unwrap();
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	// a single tree fills up to three distractors even though duplicate objects are not permitted
+	let mut tg = TargetGenerator::new(&backgrounds, &objects, &output.join("annotations.json")).unwrap();
+	tg.load_distractors(&distractors).unwrap();
+	assert_eq!(tg.config.distractors, Some(DistractorConfig::default()));
+	assert!(!tg.config.permit_duplicates);
+	
+	let counts = (0..20).map(|seed| {
+		let target = tg.generate_target(STANDARD_PPM, 1, seed).unwrap();
+		target.provenance.objects.iter().filter(|o| o.distractor).count()
+	}).collect::<Vec<usize>>();
+	assert!(counts.iter().all(|count| *count <= 3));
+	assert!(counts.iter().any(|count| *count > 1), "{:?}", counts);
+	
+	tg.generate_targets(4, ..2, &output).unwrap();
+	tg.close();
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
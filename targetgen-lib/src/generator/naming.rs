@@ -0,0 +1,154 @@
+use std::path::Path;
+use serde::Serialize;
+use crate::generator::error::GenerationError;
+
+/// How generated images are named and laid out in the output folder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutputNaming {
+	/// The file name of each image without the extension, ".png" is appended. Placeholders are written
+	/// as `{name}` or `{name:0N}` to zero-pad to N digits:
+	/// - `index` the index of the image in the run
+	/// - `run_id` the [OutputNaming::run_id], or the seed of the run in hex if that is not set
+	/// - `seed` the seed of the run
+	/// - `background` the file name of the background without its extension
+	pub template: String,
+	/// An identifier of the run for the `{run_id}` placeholder, useful to keep the names of batches
+	/// that are combined later apart
+	pub run_id: Option<String>,
+	/// If set, images are placed in numbered subfolders that each hold this many images, ex. 0003/
+	pub shard_size: Option<u32>,
+}
+
+impl Default for OutputNaming {
+	fn default() -> Self {
+		Self {
+			template: "{index}".to_string(),
+			run_id: None,
+			shard_size: None,
+		}
+	}
+}
+
+impl OutputNaming {
+	/// The path of an image relative to the output folder, always separated by "/" since it is also
+	/// the file name recorded in the annotations
+	pub fn file_name(&self, index: u32, seed: u64, background: &str) -> Result<String, GenerationError> {
+		let background = Path::new(background).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+		let run_id = self.run_id.clone().unwrap_or_else(|| format!("{:016x}", seed));
+		let name = render(&self.template, |placeholder| match placeholder {
+			"index" => Some(index.to_string()),
+			"run_id" => Some(run_id.clone()),
+			"seed" => Some(seed.to_string()),
+			"background" => Some(background.clone()),
+			_ => None,
+		})?;
+		
+		match self.shard_size {
+			Some(0) => Err(GenerationError::GenericError("The shard size must be at least 1".to_string())),
+			Some(shard_size) => Ok(format!("{:04}/{}.png", index / shard_size, name)),
+			None => Ok(format!("{}.png", name)),
+		}
+	}
+	
+	/// Check that the template is valid before a run starts. It has to contain `{index}`, without it
+	/// every image of the run would get the same name and overwrite the last.
+	pub fn validate(&self) -> Result<(), GenerationError> {
+		self.file_name(0, 0, "background.png")?;
+		
+		let has_index = std::cell::Cell::new(false);
+		render(&self.template, |placeholder| {
+			has_index.set(has_index.get() || placeholder == "index");
+			Some(String::new())
+		})?;
+		
+		if has_index.get() {
+			Ok(())
+		} else {
+			Err(GenerationError::GenericError(format!("The name template {} has no {{index}}, every image would get the same name", self.template)))
+		}
+	}
+}
+
+/// Replace every `{name}` or `{name:0N}` placeholder in [template] with the value from [lookup]
+fn render<F: Fn(&str) -> Option<String>>(template: &str, lookup: F) -> Result<String, GenerationError> {
+	let mut out = String::new();
+	let mut rest = template;
+	
+	while let Some(start) = rest.find('{') {
+		out.push_str(&rest[..start]);
+		
+		let end = rest[start..].find('}').ok_or(GenerationError::GenericError(format!("Unclosed placeholder in {}", template)))? + start;
+		let placeholder = &rest[start + 1..end];
+		let (name, width) = match placeholder.split_once(':') {
+			Some((name, format)) => {
+				let width = format.strip_prefix('0').and_then(|w| w.parse::<usize>().ok())
+					.ok_or(GenerationError::GenericError(format!("Invalid padding \"{}\" in {}, expected ex. 06", format, template)))?;
+				(name, width)
+			}
+			None => (placeholder, 0),
+		};
+		
+		let value = lookup(name).ok_or(GenerationError::GenericError(format!("Unknown placeholder {{{}}} in {}", name, template)))?;
+		out.push_str(&format!("{:0>width$}", value, width = width));
+		
+		rest = &rest[end + 1..];
+	}
+	
+	out.push_str(rest);
+	
+	Ok(out)
+}
+
+#[test]
+fn test_output_naming() {
+	let naming = OutputNaming {
+		template: "{run_id}_{background}_{index:06}".to_string(),
+		run_id: Some("batch-a".to_string()),
+		shard_size: Some(1000),
+	};
+	
+	assert_eq!(naming.file_name(12345, 7, "backgrounds/runway3.png").unwrap(), "0012/batch-a_runway3_012345.png");
+	assert_eq!(OutputNaming::default().file_name(3, 7, "grass.png").unwrap(), "3.png");
+	assert_eq!(OutputNaming { template: "{seed}-{index}".to_string(), ..Default::default() }.file_name(3, 7, "grass.png").unwrap(), "7-3.png");
+	
+	assert!(OutputNaming { template: "{nope}".to_string(), ..Default::default() }.validate().is_err());
+	assert!(OutputNaming { template: "{index".to_string(), ..Default::default() }.validate().is_err());
+	assert!(OutputNaming { template: "{index:6}".to_string(), ..Default::default() }.validate().is_err());
+	assert!(OutputNaming { template: "{run_id}_{background}".to_string(), ..Default::default() }.validate().is_err());
+	assert!(OutputNaming { template: "{run_id}_{index:06}".to_string(), ..Default::default() }.validate().is_ok());
+}
+
+#[test]
+fn test_sharded_generation() {
+	use crate::generator::coco::CocoFormatFile;
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("sharded_generation");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.export_kitti = true;
+	tg.config.naming = OutputNaming {
+		template: "{background}_{index:03}".to_string(),
+		run_id: None,
+		shard_size: Some(4),
+	};
+	tg.generate_targets(10, ..3, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	assert_eq!(file.images().len(), 10);
+	
+	for image in file.images() {
+		let shard = format!("{:04}/", image.id() / 4);
+		assert!(image.file_name().starts_with(&shard), "{} is not in {}", image.file_name(), shard);
+		assert!(output.join(image.file_name()).is_file());
+		assert!(output.join("kitti").join(image.file_name()).with_extension("txt").is_file());
+	}
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
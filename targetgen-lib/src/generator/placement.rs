@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use crate::generator::coco::BoundingBox;
+use crate::generator::difficulty::Difficulty;
+
+/// Free-form attributes of an annotated object, ex. its color, written to the annotations next to its class
+pub type Attributes = BTreeMap<String, serde_json::Value>;
+
+/// The result of generating a single target image. Holds the composited image along with the
+/// placement records that describe everything that was pasted onto it, so that the caller can
+/// annotate the image once it knows where the image is going to be written.
+#[derive(Debug, Clone)]
+pub struct GeneratedTarget {
+	pub image: RgbaImage,
+	pub placements: Vec<Placement>,
+	/// every parameter that was sampled to generate the image. `provenance.objects` holds every placed
+	/// object, including distractors that are left out of `placements`, see [ObjectProvenance::placement]
+	pub provenance: ImageProvenance,
+}
+
+/// Everything that was randomly chosen while generating a single image, enough to explain the
+/// image after the fact
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ImageProvenance {
+	/// the seed of the random number generator the image was generated with
+	pub seed: u64,
+	/// the seed of the run the image is part of, [seed] is derived from it and the index of the image
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub run_seed: Option<u64>,
+	/// the file name of the background that was used as the base of the image
+	pub background: String,
+	pub pixels_per_meter: f32,
+	/// the number of objects that were asked for, some may not have been placed if the image was too crowded
+	pub requested_objects: u16,
+	/// the classes that were asked for, when they were planned ahead instead of chosen per image
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub requested_classes: Option<Vec<u32>>,
+	/// where the image falls between 0.0 (easiest) and 1.0 (hardest) in a curriculum run
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub curriculum_level: Option<f32>,
+	/// the angle of the camera above the horizon in degrees, when it was sampled to pick the views of objects
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub camera_elevation: Option<f32>,
+	pub objects: Vec<ObjectProvenance>,
+	/// the augmentations that were applied to the whole image, the ground clutter before the objects
+	/// were placed and the rest after
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub augmentations: Vec<AugmentationRecord>,
+	#[serde(default)]
+	pub difficulty: Difficulty,
+}
+
+/// The sampled parameters of a single placed object
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ObjectProvenance {
+	/// the file name of the sprite the object was made from
+	pub sprite: String,
+	pub object_id: u16,
+	pub object_class: u32,
+	/// the top left corner of the object in pixels
+	pub x: u32,
+	pub y: u32,
+	/// the ratio between the placed width and the width of the source sprite, before rotation
+	pub scale: f32,
+	/// the rotation in degrees that was applied to the object, clockwise
+	pub rotation: u32,
+	/// the contrast between the object and the background around it, see [crate::generator::difficulty::local_contrast]
+	#[serde(default)]
+	pub contrast: f32,
+	/// the augmentations that fired for this object, in the order that they were applied
+	pub augmentations: Vec<AugmentationRecord>,
+	#[serde(default, skip_serializing_if = "Attributes::is_empty")]
+	pub attributes: Attributes,
+	/// whether the object came from the distractor library
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub distractor: bool,
+	/// the index of the object in the placements of the image, which are annotated in the same order.
+	/// Not set for a distractor that was left out of the annotations.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub placement: Option<usize>,
+}
+
+/// A single augmentation that was applied to an object along with the parameters it was given
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AugmentationRecord {
+	pub name: String,
+	pub parameters: BTreeMap<String, serde_json::Value>,
+}
+
+impl AugmentationRecord {
+	pub fn new<S: Into<String>>(name: S) -> Self {
+		Self {
+			name: name.into(),
+			parameters: BTreeMap::new(),
+		}
+	}
+	
+	pub fn with<V: Into<serde_json::Value>>(mut self, parameter: &str, value: V) -> Self {
+		self.parameters.insert(parameter.to_string(), value.into());
+		self
+	}
+}
+
+/// A record of a single object that was placed onto a generated image
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+	/// the class (category id) of the object
+	pub object_class: u32,
+	/// the location of the object in pixels, after any resizing and rotation. This may extend past
+	/// the edges of the image, see [Placement::visible_bbox]
+	pub bbox: BoundingBox,
+	/// the fraction of the bounding box that lies outside of the image, from 0.0 to 1.0
+	pub truncation: f32,
+	/// the fraction of the bounding box that is covered by objects placed after this one, from 0.0 to 1.0
+	pub occlusion: f32,
+	pub attributes: Attributes,
+	/// a distractor that is annotated so that it can be ignored, it is not a target
+	pub ignore: bool,
+}
+
+impl Placement {
+	pub fn new(object_class: u32, bbox: BoundingBox, image_width: u32, image_height: u32) -> Self {
+		let visible = Self::clip(bbox, image_width, image_height).map(|b| b.width * b.height).unwrap_or(0);
+		let total = bbox.width * bbox.height;
+		
+		Self {
+			object_class,
+			bbox,
+			truncation: if total == 0 { 0.0 } else { 1.0 - visible as f32 / total as f32 },
+			occlusion: 0.0,
+			attributes: Attributes::new(),
+			ignore: false,
+		}
+	}
+	
+	/// The area of the bounding box in pixels
+	pub fn area(&self) -> f64 {
+		(self.bbox.width * self.bbox.height) as f64
+	}
+	
+	/// The part of the bounding box that lies inside of an image of the given size, if any
+	pub fn visible_bbox(&self, image_width: u32, image_height: u32) -> Option<BoundingBox> {
+		Self::clip(self.bbox, image_width, image_height)
+	}
+	
+	fn clip(bbox: BoundingBox, image_width: u32, image_height: u32) -> Option<BoundingBox> {
+		let right = (bbox.x + bbox.width).min(image_width);
+		let bottom = (bbox.y + bbox.height).min(image_height);
+		
+		if bbox.x >= right || bbox.y >= bottom {
+			return None;
+		}
+		
+		Some(BoundingBox {
+			x: bbox.x,
+			y: bbox.y,
+			width: right - bbox.x,
+			height: bottom - bbox.y,
+		})
+	}
+}
+
+/// Fill in the occlusion of each placement from the placements that were pasted on top of it.
+/// Placements must be in the order they were pasted. Occlusion is measured over the bounding boxes,
+/// so transparent parts of the covering objects are counted as covering.
+pub fn compute_occlusion(placements: &mut [Placement]) {
+	for i in 0..placements.len() {
+		let bbox = placements[i].bbox;
+		
+		if bbox.width == 0 || bbox.height == 0 {
+			continue;
+		}
+		
+		let mut covered = vec![false; (bbox.width * bbox.height) as usize];
+		
+		for above in placements[i + 1..].iter().filter(|p| p.bbox.collides_with(&bbox)) {
+			let (left, right) = (above.bbox.x.max(bbox.x), (above.bbox.x + above.bbox.width).min(bbox.x + bbox.width));
+			let (top, bottom) = (above.bbox.y.max(bbox.y), (above.bbox.y + above.bbox.height).min(bbox.y + bbox.height));
+			
+			for y in top..bottom {
+				let row = ((y - bbox.y) * bbox.width) as usize;
+				covered[row + (left - bbox.x) as usize..row + (right - bbox.x) as usize].fill(true);
+			}
+		}
+		
+		placements[i].occlusion = covered.iter().filter(|c| **c).count() as f32 / covered.len() as f32;
+	}
+}
+
+#[test]
+fn test_truncation_and_occlusion() {
+	let bbox = |x, y| BoundingBox { x, y, width: 10, height: 10 };
+	
+	let mut placements = vec![
+		Placement::new(0, bbox(0, 0), 100, 100),
+		Placement::new(0, bbox(5, 0), 100, 100),
+		Placement::new(0, bbox(95, 95), 100, 100),
+	];
+	compute_occlusion(&mut placements);
+	
+	assert_eq!(placements[0].occlusion, 0.5);
+	assert_eq!(placements[1].occlusion, 0.0);
+	assert_eq!(placements[2].truncation, 0.75);
+	assert_eq!(placements[2].visible_bbox(100, 100), Some(BoundingBox { x: 95, y: 95, width: 5, height: 5 }));
+}
@@ -0,0 +1,309 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use serde::Serialize;
+use crate::generator::coco::CocoFormatFile;
+use crate::generator::error::GenerationError;
+use crate::generator::export::cvat::{self, CVAT_FILE};
+use crate::generator::export::label_path;
+
+/// The folders of the per-image exporters, whose files follow their image into its split
+const PER_IMAGE_FOLDERS: [&str; 4] = ["kitti", "labelme", "provenance", "yolo"];
+
+/// How to divide the generated images into train, validation and test sets. Ratios do not need to
+/// add up to 1, they are normalized. A split with a ratio of 0 is not created.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SplitConfig {
+	pub train: f32,
+	pub val: f32,
+	pub test: f32,
+	/// Assign whole backgrounds to a split instead of single images, so that no background is shared
+	/// between splits
+	pub by_background: bool,
+}
+
+impl Default for SplitConfig {
+	fn default() -> Self {
+		Self {
+			train: 0.8,
+			val: 0.1,
+			test: 0.1,
+			by_background: false,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Split {
+	Train,
+	Val,
+	Test,
+}
+
+impl Split {
+	pub const ALL: [Split; 3] = [Split::Train, Split::Val, Split::Test];
+	
+	/// The name of the split, which is also the name of its image folder
+	pub fn name(&self) -> &'static str {
+		match self {
+			Split::Train => "train",
+			Split::Val => "val",
+			Split::Test => "test",
+		}
+	}
+	
+	fn ratio(&self, config: &SplitConfig) -> f32 {
+		match self {
+			Split::Train => config.train,
+			Split::Val => config.val,
+			Split::Test => config.test,
+		}
+	}
+}
+
+/// A group of images that must end up in the same split
+struct Unit {
+	key: String,
+	image_ids: Vec<u32>,
+	class_counts: HashMap<u32, u32>,
+}
+
+/// Assign every image of a COCO file to a split. Images are assigned greedily to the split that is
+/// furthest below its share of the classes they contain (iterative stratification), with the images
+/// of the rarest classes placed first so that every class reaches every split. The assignment only
+/// depends on the contents of the file, not the order the images were generated in.
+pub fn assign_splits(file: &CocoFormatFile, config: &SplitConfig) -> Result<HashMap<u32, Split>, GenerationError> {
+	let total_ratio: f32 = Split::ALL.iter().map(|split| split.ratio(config).max(0.0)).sum();
+	
+	if total_ratio <= 0.0 {
+		return Err(GenerationError::GenericError("At least one split ratio must be positive".to_string()));
+	}
+	
+	let splits = Split::ALL.iter().copied().filter(|split| split.ratio(config) > 0.0).collect::<Vec<Split>>();
+	let ratios = splits.iter().map(|split| split.ratio(config) / total_ratio).collect::<Vec<f32>>();
+	
+	// group the images into the units that are assigned together
+	let mut units: HashMap<String, Unit> = HashMap::new();
+	for image in file.images() {
+		let key = if config.by_background {
+			image.background().unwrap_or_default().to_string()
+		} else {
+			format!("{:010}", image.id())
+		};
+		
+		units.entry(key.clone()).or_insert_with(|| Unit { key, image_ids: vec![], class_counts: HashMap::new() }).image_ids.push(image.id());
+	}
+	
+	let unit_of_image = units.values().flat_map(|unit| unit.image_ids.iter().map(|id| (*id, unit.key.clone()))).collect::<HashMap<u32, String>>();
+	let mut class_totals: HashMap<u32, u32> = HashMap::new();
+	for annotation in file.annotations() {
+		if let Some(key) = unit_of_image.get(&annotation.image_id()) {
+			*units.get_mut(key).unwrap().class_counts.entry(annotation.category_id()).or_default() += 1;
+			*class_totals.entry(annotation.category_id()).or_default() += 1;
+		}
+	}
+	
+	let mut units = units.into_values().collect::<Vec<Unit>>();
+	let rarest = |unit: &Unit| unit.class_counts.keys().map(|c| class_totals[c]).min().unwrap_or(u32::MAX);
+	if config.by_background {
+		// place the largest groups first, they are the hardest to fit
+		units.sort_by(|a, b| b.image_ids.len().cmp(&a.image_ids.len()).then(a.key.cmp(&b.key)));
+	} else {
+		units.sort_by(|a, b| rarest(a).cmp(&rarest(b)).then(a.key.cmp(&b.key)));
+	}
+	
+	let image_total = file.images().len() as f32;
+	let mut image_counts = vec![0u32; splits.len()];
+	let mut class_counts: Vec<HashMap<u32, u32>> = vec![HashMap::new(); splits.len()];
+	let mut assignment = HashMap::new();
+	
+	for unit in units {
+		let score = |s: usize| {
+			let image_deficit = (ratios[s] * image_total - image_counts[s] as f32) / image_total.max(1.0);
+			let class_deficit: f32 = unit.class_counts.keys().map(|c| {
+				let current = class_counts[s].get(c).copied().unwrap_or(0) as f32;
+				(ratios[s] * class_totals[c] as f32 - current) / class_totals[c] as f32
+			}).sum();
+			
+			image_deficit + class_deficit
+		};
+		
+		// prefer splits that still have room for the images of this unit
+		let has_room = |s: usize| image_counts[s] as f32 + unit.image_ids.len() as f32 <= (ratios[s] * image_total).ceil();
+		let candidates = if (0..splits.len()).any(has_room) && !config.by_background {
+			(0..splits.len()).filter(|s| has_room(*s)).collect::<Vec<usize>>()
+		} else {
+			(0..splits.len()).collect::<Vec<usize>>()
+		};
+		
+		let chosen = candidates.into_iter().fold(None, |best: Option<(usize, f32)>, s| {
+			let score = score(s);
+			match best {
+				Some((_, best_score)) if best_score >= score => best,
+				_ => Some((s, score)),
+			}
+		}).unwrap().0;
+		
+		image_counts[chosen] += unit.image_ids.len() as u32;
+		for (class, count) in &unit.class_counts {
+			*class_counts[chosen].entry(*class).or_default() += count;
+		}
+		for id in unit.image_ids {
+			assignment.insert(id, splits[chosen]);
+		}
+	}
+	
+	Ok(assignment)
+}
+
+/// Divide a generated dataset into splits. Each split gets its own folder inside of [output] that the
+/// images and their per-image label files are moved into, and its own COCO file next to
+/// [annotations_path], ex. annotations.json -> annotations_train.json. The file names in [file], and in
+/// the CVAT file if there is one, are updated to be relative to [output].
+pub fn split_dataset(output: &Path, annotations_path: &Path, file: &mut CocoFormatFile, config: &SplitConfig) -> Result<HashMap<Split, CocoFormatFile>, GenerationError> {
+	let assignment = assign_splits(file, config)?;
+	let stem = annotations_path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("annotations".to_string());
+	let mut split_files = HashMap::new();
+	
+	for split in Split::ALL.iter().filter(|split| split.ratio(config) > 0.0) {
+		let folder = output.join(split.name());
+		fs::create_dir_all(&folder)?;
+		
+		let ids = assignment.iter().filter(|(_, s)| *s == split).map(|(id, _)| *id).collect::<HashSet<u32>>();
+		let split_file = file.subset(&ids);
+		
+		for image in split_file.images() {
+			move_file(&output.join(image.file_name()), &folder.join(image.file_name()))?;
+			
+			for sub in PER_IMAGE_FOLDERS {
+				for extension in ["txt", "json"] {
+					let label = label_path(&output.join(sub), image.file_name(), extension);
+					if label.is_file() {
+						move_file(&label, &label_path(&folder.join(sub), image.file_name(), extension))?;
+					}
+				}
+			}
+		}
+		
+		split_file.save(annotations_path.with_file_name(format!("{}_{}.json", stem, split.name())))?;
+		split_files.insert(*split, split_file);
+	}
+	
+	for sub in PER_IMAGE_FOLDERS {
+		remove_empty_folders(&output.join(sub));
+	}
+	
+	for image in file.images_mut() {
+		if let Some(split) = assignment.get(&image.id()) {
+			let name = format!("{}/{}", split.name(), image.file_name());
+			image.set_file_name(name);
+		}
+	}
+	
+	if output.join(CVAT_FILE).is_file() {
+		let names = file.images().iter().map(|image| (image.id(), image.file_name().to_string())).collect();
+		cvat::rename_images(&output.join(CVAT_FILE), &names)?;
+	}
+	
+	Ok(split_files)
+}
+
+/// Move a file, creating the folder it is moved into and removing the folder it was in if it is left empty
+fn move_file(from: &Path, to: &Path) -> Result<(), GenerationError> {
+	if let Some(parent) = to.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	
+	fs::rename(from, to)?;
+	
+	if let Some(parent) = from.parent() {
+		let _ = fs::remove_dir(parent); // fails unless the folder is empty
+	}
+	
+	Ok(())
+}
+
+/// Remove a folder and every folder inside of it, as long as none of them hold any files
+fn remove_empty_folders(folder: &Path) {
+	if let Ok(entries) = fs::read_dir(folder) {
+		for entry in entries.flatten() {
+			if entry.path().is_dir() {
+				remove_empty_folders(&entry.path());
+			}
+		}
+	}
+	
+	let _ = fs::remove_dir(folder); // fails unless the folder is empty
+}
+
+#[test]
+fn test_stratified_splits() {
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("stratified_splits");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.export_kitti = true;
+	tg.config.export_cvat = true;
+	tg.config.splits = Some(SplitConfig { train: 0.6, val: 0.2, test: 0.2, by_background: false });
+	tg.generate_targets(30, ..4, &output).unwrap();
+	tg.close();
+	
+	let combined = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	let mut total = 0;
+	
+	for (split, expected) in [(Split::Train, 18), (Split::Val, 6), (Split::Test, 6)] {
+		let file = CocoFormatFile::load(output.join(format!("annotations_{}.json", split.name()))).unwrap();
+		assert_eq!(file.images().len(), expected);
+		total += file.images().len();
+		
+		for category in file.categories() {
+			assert!(file.annotations().iter().any(|a| a.category_id() == category.id()), "{} missing from {}", category.name(), split.name());
+		}
+		
+		for image in file.images() {
+			assert!(output.join(split.name()).join(image.file_name()).is_file());
+			assert!(output.join(split.name()).join("kitti").join(format!("{}.txt", image.id())).is_file());
+			assert!(combined.images().iter().any(|c| c.file_name() == format!("{}/{}", split.name(), image.file_name())));
+		}
+	}
+	
+	assert_eq!(total, 30);
+	assert!(!output.join("kitti").exists());
+	
+	// the CVAT file follows the images into their splits
+	let xml = fs::read_to_string(output.join(CVAT_FILE)).unwrap();
+	for image in combined.images() {
+		assert!(xml.contains(&format!("<image id=\"{}\" name=\"{}\"", image.id(), image.file_name())), "{} is not renamed", image.file_name());
+		assert!(output.join(image.file_name()).is_file());
+	}
+	
+	fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_split_by_background() {
+	use crate::generator::coco::{BoundingBox, CocoCategory, CocoGenerator};
+	
+	let mut coco = CocoGenerator::new("unused.json", vec![CocoCategory::new(0, "car".to_string())]);
+	for i in 0..20 {
+		coco.add_image(i, 10, 10, format!("{}.png", i), format!("background_{}.png", i % 4), None);
+		coco.add_annotation(i, 0, 0, vec![], 1.0, BoundingBox { x: 0, y: 0, width: 1, height: 1 });
+	}
+	
+	let config = SplitConfig { train: 0.5, val: 0.5, test: 0.0, by_background: true };
+	let assignment = assign_splits(&coco.file, &config).unwrap();
+	
+	let mut backgrounds: HashMap<String, Split> = HashMap::new();
+	for image in coco.file.images() {
+		let split = assignment[&image.id()];
+		assert_ne!(split, Split::Test);
+		assert_eq!(*backgrounds.entry(image.background().unwrap().to_string()).or_insert(split), split);
+	}
+	assert_eq!(assignment.values().filter(|s| **s == Split::Train).count(), 10);
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::generator::coco::CocoFormatFile;
+use crate::generator::error::GenerationError;
+
+/// The number of bins in each bounding box size histogram
+const HISTOGRAM_BINS: usize = 20;
+/// The number of cells along each side of the placement heatmap
+const HEATMAP_SIZE: usize = 16;
+
+/// Statistics about a generated dataset, computed from its COCO file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetStats {
+	pub images: usize,
+	pub annotations: usize,
+	/// images that are in the COCO file but could not be found on disk
+	pub missing_images: Vec<String>,
+	/// number of instances per category name
+	pub class_counts: BTreeMap<String, u32>,
+	/// number of objects in an image -> number of images with that many objects
+	pub objects_per_image: BTreeMap<u32, u32>,
+	/// bounding box sizes in pixels
+	pub bbox_width: Histogram,
+	pub bbox_height: Histogram,
+	pub bbox_area: Histogram,
+	/// number of images generated from each background
+	pub background_counts: BTreeMap<String, u32>,
+	/// where the centers of objects fall, relative to the size of their image
+	pub heatmap: Heatmap,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+	pub min: f64,
+	pub max: f64,
+	pub mean: f64,
+	/// the width of each bin, bin i holds values in [i * bin_width, (i + 1) * bin_width)
+	pub bin_width: f64,
+	pub counts: Vec<u32>,
+}
+
+impl Histogram {
+	pub fn new(values: &[f64]) -> Self {
+		if values.is_empty() {
+			return Self { min: 0.0, max: 0.0, mean: 0.0, bin_width: 0.0, counts: vec![] };
+		}
+		
+		let min = values.iter().copied().fold(f64::MAX, f64::min);
+		let max = values.iter().copied().fold(f64::MIN, f64::max);
+		let bin_width = (max / HISTOGRAM_BINS as f64).ceil().max(1.0);
+		let mut counts = vec![0; HISTOGRAM_BINS];
+		
+		for value in values {
+			counts[((value / bin_width) as usize).min(HISTOGRAM_BINS - 1)] += 1;
+		}
+		
+		Self {
+			min,
+			max,
+			mean: values.iter().sum::<f64>() / values.len() as f64,
+			bin_width,
+			counts,
+		}
+	}
+}
+
+/// A grid over the image, cells[row][column] counts the objects whose center falls in that cell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Heatmap {
+	pub rows: usize,
+	pub columns: usize,
+	pub cells: Vec<Vec<u32>>,
+}
+
+impl DatasetStats {
+	/// Compute the statistics of a COCO file, images are looked for relative to [images_folder]
+	pub fn compute<P: AsRef<Path>>(file: &CocoFormatFile, images_folder: P) -> Self {
+		let names = file.categories().iter().map(|c| (c.id(), c.name().to_string())).collect::<BTreeMap<u32, String>>();
+		let images = file.images().iter().map(|i| (i.id(), i)).collect::<BTreeMap<_, _>>();
+		
+		let mut class_counts = BTreeMap::new();
+		let mut per_image: BTreeMap<u32, u32> = images.keys().map(|id| (*id, 0)).collect();
+		let mut heatmap = vec![vec![0; HEATMAP_SIZE]; HEATMAP_SIZE];
+		let (mut widths, mut heights, mut areas) = (vec![], vec![], vec![]);
+		
+		for annotation in file.annotations() {
+			let name = names.get(&annotation.category_id()).cloned().unwrap_or(annotation.category_id().to_string());
+			*class_counts.entry(name).or_insert(0) += 1;
+			*per_image.entry(annotation.image_id()).or_insert(0) += 1;
+			
+			let bbox = annotation.bbox();
+			widths.push(bbox.width as f64);
+			heights.push(bbox.height as f64);
+			areas.push((bbox.width * bbox.height) as f64);
+			
+			if let Some(image) = images.get(&annotation.image_id()) {
+				let center_x = (bbox.x as f64 + bbox.width as f64 / 2.0) / image.width() as f64;
+				let center_y = (bbox.y as f64 + bbox.height as f64 / 2.0) / image.height() as f64;
+				let column = ((center_x * HEATMAP_SIZE as f64) as usize).min(HEATMAP_SIZE - 1);
+				let row = ((center_y * HEATMAP_SIZE as f64) as usize).min(HEATMAP_SIZE - 1);
+				heatmap[row][column] += 1;
+			}
+		}
+		
+		let mut objects_per_image = BTreeMap::new();
+		for count in per_image.values() {
+			*objects_per_image.entry(*count).or_insert(0) += 1;
+		}
+		
+		let mut background_counts = BTreeMap::new();
+		for image in file.images() {
+			let background = image.background().map(|b| Path::new(b).file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or(b.to_string()));
+			*background_counts.entry(background.unwrap_or("unknown".to_string())).or_insert(0) += 1;
+		}
+		
+		Self {
+			images: file.images().len(),
+			annotations: file.annotations().len(),
+			missing_images: file.images().iter().filter(|i| !images_folder.as_ref().join(i.file_name()).is_file()).map(|i| i.file_name().to_string()).collect(),
+			class_counts,
+			objects_per_image,
+			bbox_width: Histogram::new(&widths),
+			bbox_height: Histogram::new(&heights),
+			bbox_area: Histogram::new(&areas),
+			background_counts,
+			heatmap: Heatmap {
+				rows: HEATMAP_SIZE,
+				columns: HEATMAP_SIZE,
+				cells: heatmap,
+			},
+		}
+	}
+	
+	/// Compute the statistics of an annotations file on disk, with the images relative to the file
+	pub fn load<P: AsRef<Path>>(annotations_path: P) -> Result<Self, GenerationError> {
+		let file = CocoFormatFile::load(&annotations_path)?;
+		let folder = annotations_path.as_ref().parent().map(|p| p.to_path_buf()).unwrap_or_default();
+		
+		Ok(Self::compute(&file, folder))
+	}
+	
+	/// Write the statistics as JSON and as a self-contained HTML report
+	pub fn save<P: AsRef<Path>>(&self, json_path: P, html_path: P) -> Result<(), GenerationError> {
+		serde_json::to_writer_pretty(std::fs::File::create(json_path)?, self)?;
+		std::fs::write(html_path, self.to_html())?;
+		
+		Ok(())
+	}
+	
+	/// Render a simple HTML report that does not depend on anything outside of the file
+	pub fn to_html(&self) -> String {
+		let mut html = String::new();
+		
+		html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Dataset statistics</title>\n<style>\n");
+		html.push_str("body { font-family: sans-serif; margin: 2em; }\n");
+		html.push_str("table { border-collapse: collapse; margin-bottom: 2em; }\n");
+		html.push_str("td, th { padding: 2px 8px; text-align: left; }\n");
+		html.push_str(".bar { background: #4a7ebb; height: 14px; }\n");
+		html.push_str(".heatmap td { width: 24px; height: 24px; padding: 0; }\n");
+		html.push_str("</style>\n</head>\n<body>\n<h1>Dataset statistics</h1>\n");
+		
+		writeln!(html, "<p>{} images, {} annotations, {} missing images</p>", self.images, self.annotations, self.missing_images.len()).unwrap();
+		
+		let counts = |map: &BTreeMap<String, u32>| map.iter().map(|(k, v)| (escape(k), *v)).collect::<Vec<(String, u32)>>();
+		bar_table(&mut html, "Instances per class", &counts(&self.class_counts));
+		bar_table(&mut html, "Objects per image", &self.objects_per_image.iter().map(|(k, v)| (k.to_string(), *v)).collect::<Vec<_>>());
+		histogram_table(&mut html, "Bounding box width (px)", &self.bbox_width);
+		histogram_table(&mut html, "Bounding box height (px)", &self.bbox_height);
+		histogram_table(&mut html, "Bounding box area (px)", &self.bbox_area);
+		bar_table(&mut html, "Images per background", &counts(&self.background_counts));
+		
+		let max = self.heatmap.cells.iter().flatten().copied().max().unwrap_or(0).max(1);
+		html.push_str("<h2>Placement heatmap (object centers)</h2>\n<table class=\"heatmap\">\n");
+		for row in &self.heatmap.cells {
+			html.push_str("<tr>");
+			for cell in row {
+				let intensity = 255 - (*cell as f64 / max as f64 * 255.0) as u8;
+				write!(html, "<td title=\"{}\" style=\"background: rgb({}, {}, 255)\"></td>", cell, intensity, intensity).unwrap();
+			}
+			html.push_str("</tr>\n");
+		}
+		html.push_str("</table>\n</body>\n</html>\n");
+		
+		html
+	}
+}
+
+fn bar_table(html: &mut String, title: &str, rows: &[(String, u32)]) {
+	let max = rows.iter().map(|(_, v)| *v).max().unwrap_or(0).max(1);
+	
+	writeln!(html, "<h2>{}</h2>\n<table>", title).unwrap();
+	for (label, value) in rows {
+		writeln!(html, "<tr><td>{}</td><td>{}</td><td><div class=\"bar\" style=\"width: {}px\"></div></td></tr>", label, value, value * 400 / max).unwrap();
+	}
+	html.push_str("</table>\n");
+}
+
+fn histogram_table(html: &mut String, title: &str, histogram: &Histogram) {
+	let rows = histogram.counts.iter().enumerate().map(|(i, count)| {
+		(format!("{} - {}", i as f64 * histogram.bin_width, (i + 1) as f64 * histogram.bin_width), *count)
+	}).collect::<Vec<(String, u32)>>();
+	
+	bar_table(html, &format!("{} (min {}, mean {:.1}, max {})", title, histogram.min, histogram.mean, histogram.max), &rows);
+}
+
+fn escape(s: &str) -> String {
+	s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[test]
+fn test_dataset_stats() {
+	use crate::generator::coco::{BoundingBox, CocoCategory, CocoGenerator};
+	
+	let mut coco = CocoGenerator::new("unused.json", vec![CocoCategory::new(0, "car".to_string()), CocoCategory::new(1, "tire".to_string())]);
+	coco.add_image(0, 100, 100, "0.png".to_string(), "backgrounds/grass.png".to_string(), None);
+	coco.add_annotation(0, 0, 0, vec![], 200.0, BoundingBox { x: 0, y: 0, width: 20, height: 10 });
+	coco.add_annotation(0, 1, 0, vec![], 100.0, BoundingBox { x: 90, y: 90, width: 10, height: 10 });
+	coco.add_image(1, 100, 100, "1.png".to_string(), "backgrounds/grass.png".to_string(), None);
+	coco.add_image(2, 100, 100, "2.png".to_string(), "backgrounds/runway.png".to_string(), None);
+	coco.add_annotation(2, 1, 0, vec![], 100.0, BoundingBox { x: 45, y: 45, width: 10, height: 10 });
+	
+	let stats = DatasetStats::compute(&coco.file, "does_not_exist");
+	
+	assert_eq!(stats.missing_images.len(), 3);
+	assert_eq!(stats.class_counts, BTreeMap::from([("car".to_string(), 1), ("tire".to_string(), 2)]));
+	assert_eq!(stats.objects_per_image, BTreeMap::from([(0, 1), (1, 1), (2, 1)]));
+	assert_eq!(stats.background_counts, BTreeMap::from([("grass.png".to_string(), 2), ("runway.png".to_string(), 1)]));
+	assert_eq!((stats.bbox_width.min, stats.bbox_width.max), (10.0, 20.0));
+	assert_eq!(stats.bbox_width.counts.iter().sum::<u32>(), 3);
+	assert_eq!(stats.heatmap.cells[0][1], 1);
+	assert_eq!(stats.heatmap.cells[15][15], 1);
+	assert_eq!(stats.heatmap.cells[8][8], 1);
+	assert!(stats.to_html().contains("<td>tire</td><td>2</td>"));
+}
@@ -32,6 +32,30 @@ pub fn new_sizes(object_width: u32, object_height: u32, pixels_per_meter: f32, r
 	Ok((new_width, new_height))
 }
 
+/// Derive the seed of a single image from the seed of the run and the index of the image, so that an
+/// image does not depend on which thread generated it or in what order (SplitMix64 finalizer)
+pub fn image_seed(run_seed: u64, index: u32) -> u64 {
+	let mut z = run_seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+	z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+	z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+	z ^ (z >> 31)
+}
+
+/// Mixed into the run seed to give the object counts their own stream of seeds
+const COUNT_STREAM: u64 = 0x6A09_E667_F3BC_C908;
+
+/// Derive the seed that the object count of an image is drawn from. The count must not come from
+/// [image_seed], whose first random value already picks the background of the image.
+pub fn count_seed(run_seed: u64, index: u32) -> u64 {
+	image_seed(run_seed ^ COUNT_STREAM, index)
+}
+
+/// The multiple of 90 degrees that turns a view facing [view_yaw] closest to [heading], rounded so that a
+/// heading just short of the view, ex. 355 against 0, is not turned almost all the way around
+pub fn residual_rotation(heading: i32, view_yaw: i32) -> i32 {
+	(heading - view_yaw + 45).rem_euclid(360) / 90 * 90
+}
+
 pub fn rotate_90s(image: &DynamicImage, angle: i32) -> DynamicImage {
 	let mut i = image.clone();
 	
@@ -59,7 +83,7 @@ pub fn post_rotate_dimension(width: u32, height: u32, angle: f32) -> (u32, u32)
 }
 
 // TODO: various problems with this at present. Cuts off the image mostly in multiple ways
-pub fn rotate_image(image: &DynamicImage, angle: f32) -> DynamicImage {
+pub fn rotate_image(_image: &DynamicImage, _angle: f32) -> DynamicImage {
 	// 
 	
 	todo!()
@@ -74,19 +98,59 @@ pub fn cleanup_output<P: AsRef<Path>>(folder_path: P) -> std::io::Result<()> {
 		let entry = entry?;
 		let path = entry.path();
 		
-		if entry.file_type()?.is_file() && (is_image_type(path.to_str().unwrap()) || path.extension().unwrap() == "json") {
+		if entry.file_type()?.is_file() && (is_image_type(path.to_str().unwrap()) || path.extension().is_some_and(|e| e == "json" || e == "jsonl")) {
 			fs::remove_file(entry.path())?;
 		}
 	}
 	Ok(())
 }
 
+/// Create a fresh, empty scratch directory for a test
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> std::path::PathBuf {
+	let dir = std::env::temp_dir().join(format!("targetgen_{}_{}", name, std::process::id()));
+	let _ = fs::remove_dir_all(&dir);
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+/// Write a small set of backgrounds and objects into [dir] that is cheap to generate from.
+/// Returns the backgrounds and objects directories.
+#[cfg(test)]
+pub(crate) fn create_test_assets(dir: &Path) -> (std::path::PathBuf, std::path::PathBuf) {
+	use image::{Rgba, RgbaImage};
+	
+	let backgrounds = dir.join("backgrounds");
+	let objects = dir.join("objects");
+	fs::create_dir_all(&backgrounds).unwrap();
+	fs::create_dir_all(&objects).unwrap();
+	
+	RgbaImage::from_pixel(320, 240, Rgba([60, 120, 40, 255])).save(backgrounds.join("grass.png")).unwrap();
+	RgbaImage::from_pixel(300, 200, Rgba([110, 110, 110, 255])).save(backgrounds.join("runway.png")).unwrap();
+	
+	RgbaImage::from_pixel(40, 20, Rgba([200, 30, 30, 255])).save(objects.join("car_1.png")).unwrap();
+	RgbaImage::from_pixel(30, 30, Rgba([20, 20, 20, 255])).save(objects.join("tire_1.png")).unwrap();
+	
+	fs::write(objects.join("objects.json"), r#"{
+  "object_images": {
+    "car_1.png": { "ground_width": 1.0, "object_type": 7 },
+    "tire_1.png": { "ground_width": 0.6, "object_type": 1 }
+  },
+  "object_types": {
+    "1": { "name": "tire" },
+    "7": { "name": "car" }
+  }
+}"#).unwrap();
+	
+	(backgrounds, objects)
+}
+
 #[test]
 fn test_is_image_type() {
-	assert_eq!(is_image_type("test.png"), true);
-	assert_eq!(is_image_type("test.jpg"), true);
-	assert_eq!(is_image_type("test.jpeg"), true);
-	assert_eq!(is_image_type("test.txt"), false);
+	assert!(is_image_type("test.png"));
+	assert!(is_image_type("test.jpg"));
+	assert!(is_image_type("test.jpeg"));
+	assert!(!is_image_type("test.txt"));
 }
 
 #[test]
@@ -95,4 +159,4 @@ fn test_resize_ratio() {
 	assert_eq!(resize_ratio(1.0, 70.0), 70.0);
 	assert_eq!(resize_ratio(1.0, 105.0), 105.0);
 	assert_eq!(resize_ratio(2.0, 140.0), 280.0);
-}
\ No newline at end of file
+}
@@ -2,4 +2,5 @@
 
 pub mod backgrounds;
 pub mod objects;
+pub mod standard_targets;
 pub mod generator;
\ No newline at end of file
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::generator::error::GenerationError;
+use crate::generator::placement::Attributes;
+use crate::generator::util;
+use crate::objects::recolor::RecolorOptions;
+use crate::objects::{ObjectDetails, ObjectDetailsFile, ObjectType};
+
+/// The optional file in each class folder of a folder-per-class library
+pub const CLASS_FILE: &str = "class.json";
+/// The ground width of the objects of a class folder without a class.json
+pub const DEFAULT_GROUND_WIDTH: f32 = 1.0;
+
+/// The contents of a class.json, applies to every sprite in the folder
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct ClassDetails {
+	/// the real width of the objects in meters, defaults to [DEFAULT_GROUND_WIDTH]
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub ground_width: Option<f32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub weight: Option<f32>,
+	#[serde(default, skip_serializing_if = "Attributes::is_empty")]
+	pub attributes: Attributes,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub recolor: Option<RecolorOptions>,
+}
+
+/// The class folders of a folder-per-class library, sorted by name
+pub fn class_folders<P: AsRef<Path>>(objects_path: P) -> Result<Vec<PathBuf>, GenerationError> {
+	let mut folders = std::fs::read_dir(objects_path)?
+		.map(|entry| entry.map(|entry| entry.path()))
+		.collect::<Result<Vec<_>, _>>()?;
+	folders.retain(|path| path.is_dir());
+	folders.sort();
+	
+	Ok(folders)
+}
+
+/// Build the equivalent of objects.json from a library laid out as `<class_name>/*.png`. Class ids are
+/// assigned in order of the sorted folder names so they are stable as long as no class is added before them.
+/// Sprites are keyed by their path relative to the library, ex. `car/car_1.png`.
+pub fn from_folders<P: AsRef<Path>>(objects_path: P) -> Result<ObjectDetailsFile, GenerationError> {
+	let mut object_images = HashMap::new();
+	let mut object_types = HashMap::new();
+	
+	for (object_type, folder) in class_folders(objects_path)?.into_iter().enumerate() {
+		let object_type = object_type as u32;
+		let name = folder.file_name().unwrap().to_string_lossy().to_string();
+		
+		let class = match std::fs::read_to_string(folder.join(CLASS_FILE)) {
+			Ok(file) => serde_json::from_str::<ClassDetails>(&file)?,
+			Err(_) => {
+				warn!("{} has no {}, using a ground width of {}m", folder.display(), CLASS_FILE, DEFAULT_GROUND_WIDTH);
+				ClassDetails::default()
+			}
+		};
+		
+		for entry in std::fs::read_dir(&folder)? {
+			let path = entry?.path();
+			if !path.is_file() || !util::is_image_type(&path.to_string_lossy()) {
+				continue;
+			}
+			
+			object_images.insert(format!("{}/{}", name, path.file_name().unwrap().to_string_lossy()), ObjectDetails {
+				ground_width: class.ground_width.unwrap_or(DEFAULT_GROUND_WIDTH),
+				object_type,
+				attributes: Attributes::new(),
+				views: vec![],
+			});
+		}
+		
+		object_types.insert(object_type, ObjectType {
+			name,
+			weight: class.weight,
+			attributes: class.attributes,
+			recolor: class.recolor,
+		});
+	}
+	
+	Ok(ObjectDetailsFile {
+		object_images,
+		object_types,
+	})
+}
+
+#[test]
+fn test_folder_library() {
+	use image::{Rgba, RgbaImage};
+	use crate::objects::ObjectManager;
+	
+	let dir = util::test_dir("folder_library");
+	let objects = dir.join("objects");
+	for class in ["tire", "car"] {
+		std::fs::create_dir_all(objects.join(class)).unwrap();
+	}
+	RgbaImage::from_pixel(40, 20, Rgba([200, 30, 30, 255])).save(objects.join("car/car_1.png")).unwrap();
+	RgbaImage::from_pixel(40, 20, Rgba([30, 30, 200, 255])).save(objects.join("car/car_2.png")).unwrap();
+	RgbaImage::from_pixel(30, 30, Rgba([20, 20, 20, 255])).save(objects.join("tire/tire_1.png")).unwrap();
+	std::fs::write(objects.join("car/class.json"), r#"{ "ground_width": 4.5, "attributes": { "kind": "vehicle" } }"#).unwrap();
+	
+	// classes are numbered by folder name, a folder without class.json gets the default width
+	let details = ObjectDetailsFile::load(&objects).unwrap();
+	assert_eq!(details.object_types[&0].name, "car");
+	assert_eq!(details.object_types[&0].attributes["kind"], "vehicle");
+	assert_eq!(details.object_types[&1].name, "tire");
+	assert_eq!(details.object_images["car/car_2.png"].ground_width, 4.5);
+	assert_eq!(details.object_images["tire/tire_1.png"], ObjectDetails { ground_width: DEFAULT_GROUND_WIDTH, object_type: 1, attributes: Attributes::new(), views: vec![] });
+	
+	let mut manager = ObjectManager::new(&objects);
+	manager.load_objects().unwrap();
+	assert_eq!(manager.sprite_paths(), vec![objects.join("car/car_1.png"), objects.join("car/car_2.png"), objects.join("tire/tire_1.png")]);
+	assert_eq!(manager.details_paths().unwrap(), vec![objects.join("car/class.json")]);
+	
+	// the exported objects.json describes the same library
+	details.save(&objects).unwrap();
+	assert_eq!(ObjectDetailsFile::load(&objects).unwrap(), details);
+	let mut exported = ObjectManager::new(&objects);
+	exported.load_objects().unwrap();
+	assert_eq!(exported.sprite_paths(), manager.sprite_paths());
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
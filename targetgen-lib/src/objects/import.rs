@@ -0,0 +1,216 @@
+use std::collections::hash_map::Entry;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use image::{Rgba, RgbaImage};
+use crate::generator::error::GenerationError;
+use crate::generator::placement::Attributes;
+use crate::objects::{ObjectDetails, ObjectDetailsFile, ObjectType};
+
+/// How the background of a photo is told apart from the object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackgroundRemoval {
+	/// every pixel close to the key color is removed, for chroma-key backdrops
+	ChromaKey,
+	/// only pixels close to the key color that are connected to the border of the photo are removed, so
+	/// parts of the object that happen to match the background are kept
+	#[default]
+	FloodFill,
+}
+
+/// The settings of an object import
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportOptions {
+	/// the object type of the imported sprite
+	pub object_type: u32,
+	/// the name of the object type, only used if the type is not defined in objects.json yet
+	pub type_name: Option<String>,
+	/// the width of the object on the ground in meters
+	pub ground_width: f32,
+	pub removal: BackgroundRemoval,
+	/// the RGB color of the background, estimated from the border of the photo if not set
+	pub key_color: Option<[u8; 3]>,
+	/// how far (RGB distance) a pixel may be from the key color to count as background
+	pub tolerance: f32,
+	/// the distance past [ImportOptions::tolerance] over which edge pixels fade from transparent to opaque
+	pub feather: f32,
+	/// the file name of the sprite in the objects folder, defaults to the name of the photo as a png
+	pub file_name: Option<String>,
+}
+
+impl Default for ImportOptions {
+	fn default() -> Self {
+		Self {
+			object_type: 0,
+			type_name: None,
+			ground_width: 1.0,
+			removal: BackgroundRemoval::default(),
+			key_color: None,
+			tolerance: 40.0,
+			feather: 120.0,
+			file_name: None,
+		}
+	}
+}
+
+/// Cut the object out of a photo, save it as a sprite in [objects_path] and register it in objects.json.
+/// Returns the path of the new sprite.
+pub fn import_object<P: AsRef<Path>, Q: AsRef<Path>>(photo: P, objects_path: Q, options: &ImportOptions) -> Result<PathBuf, GenerationError> {
+	let folder = objects_path.as_ref();
+	let mut details = ObjectDetailsFile::load(folder)?;
+	
+	let file_name = options.file_name.clone().unwrap_or_else(|| {
+		let stem = photo.as_ref().file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or("object".to_string());
+		format!("{}.png", stem)
+	});
+	let path = folder.join(&file_name);
+	
+	if path.exists() || details.object_images.contains_key(&file_name) {
+		return Err(GenerationError::GenericError(format!("An object named {} already exists", file_name)));
+	}
+	
+	if let Entry::Vacant(entry) = details.object_types.entry(options.object_type) {
+		let name = options.type_name.clone().ok_or(GenerationError::GenericError(format!("Object type {} is not defined in objects.json, give it a name", options.object_type)))?;
+		entry.insert(ObjectType { name, weight: None, attributes: Attributes::new(), recolor: None });
+	}
+	
+	let sprite = remove_background(&image::open(photo.as_ref())?.to_rgba8(), options);
+	sprite.save(&path)?;
+	
+	details.object_images.insert(file_name, ObjectDetails {
+		ground_width: options.ground_width,
+		object_type: options.object_type,
+		attributes: Attributes::new(),
+		views: vec![],
+	});
+	details.save(folder)?;
+	
+	Ok(path)
+}
+
+/// Make the background of a photo transparent. Pixels within the tolerance of the key color are
+/// removed, pixels within the feather band past it become partly transparent and have the key color
+/// unmixed from them so that no colored fringe is left around the object.
+pub fn remove_background(photo: &RgbaImage, options: &ImportOptions) -> RgbaImage {
+	let key = options.key_color.map(|[r, g, b]| Rgba([r, g, b, 255])).unwrap_or_else(|| border_color(photo));
+	let alpha_of = |pixel: &Rgba<u8>| ((distance(pixel, &key) - options.tolerance) / options.feather.max(1.0)).clamp(0.0, 1.0);
+	let (w, h) = photo.dimensions();
+	
+	// the share of each pixel that belongs to the background, 0.0 for the object
+	let mut background = vec![0.0f32; (w * h) as usize];
+	
+	match options.removal {
+		BackgroundRemoval::ChromaKey => {
+			for (x, y, pixel) in photo.enumerate_pixels() {
+				background[(y * w + x) as usize] = 1.0 - alpha_of(pixel);
+			}
+		}
+		BackgroundRemoval::FloodFill => {
+			// fill inwards from the border through pixels that are at least partly background
+			let mut queue = (0..w).flat_map(|x| [(x, 0), (x, h - 1)]).chain((0..h).flat_map(|y| [(0, y), (w - 1, y)])).collect::<VecDeque<(u32, u32)>>();
+			let mut visited = vec![false; (w * h) as usize];
+			
+			while let Some((x, y)) = queue.pop_front() {
+				let index = (y * w + x) as usize;
+				if visited[index] {
+					continue;
+				}
+				visited[index] = true;
+				
+				let share = 1.0 - alpha_of(photo.get_pixel(x, y));
+				if share <= 0.0 {
+					continue; // reached the object
+				}
+				background[index] = share;
+				
+				// only keep going through pixels that are mostly background, the feathered edge stops the fill
+				if share >= 0.5 {
+					for (nx, ny) in [(x.wrapping_sub(1), y), (x + 1, y), (x, y.wrapping_sub(1)), (x, y + 1)] {
+						if nx < w && ny < h && !visited[(ny * w + nx) as usize] {
+							queue.push_back((nx, ny));
+						}
+					}
+				}
+			}
+		}
+	}
+	
+	let mut sprite = photo.clone();
+	for (x, y, pixel) in sprite.enumerate_pixels_mut() {
+		let alpha = 1.0 - background[(y * w + x) as usize];
+		
+		if alpha <= 0.0 {
+			*pixel = Rgba([0, 0, 0, 0]);
+		} else if alpha < 1.0 {
+			// unmix the key color: observed = alpha * object + (1 - alpha) * key
+			for c in 0..3 {
+				pixel[c] = ((pixel[c] as f32 - (1.0 - alpha) * key[c] as f32) / alpha).round().clamp(0.0, 255.0) as u8;
+			}
+			pixel[3] = (alpha * pixel[3] as f32).round() as u8;
+		}
+	}
+	
+	sprite
+}
+
+/// The per channel median of the pixels along the border of an image
+fn border_color(image: &RgbaImage) -> Rgba<u8> {
+	let (w, h) = image.dimensions();
+	let border = (0..w).flat_map(|x| [(x, 0), (x, h - 1)]).chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]))
+		.map(|(x, y)| *image.get_pixel(x, y))
+		.collect::<Vec<Rgba<u8>>>();
+	
+	let median = |c: usize| {
+		let mut values = border.iter().map(|p| p[c]).collect::<Vec<u8>>();
+		values.sort();
+		values[values.len() / 2]
+	};
+	
+	Rgba([median(0), median(1), median(2), 255])
+}
+
+fn distance(a: &Rgba<u8>, b: &Rgba<u8>) -> f32 {
+	(0..3).map(|c| (a[c] as f32 - b[c] as f32).powi(2)).sum::<f32>().sqrt()
+}
+
+#[test]
+fn test_import_object() {
+	use crate::generator::util;
+	
+	let dir = util::test_dir("import_object");
+	let (_, objects) = util::create_test_assets(&dir);
+	
+	// a red box with a half green edge and a green hole in the middle, on a green screen
+	let mut photo = RgbaImage::from_pixel(60, 40, Rgba([30, 200, 40, 255]));
+	for (x, y, pixel) in photo.enumerate_pixels_mut() {
+		if (10..50).contains(&x) && (10..30).contains(&y) {
+			*pixel = if (28..32).contains(&x) && (18..22).contains(&y) { Rgba([30, 200, 40, 255]) } else { Rgba([200, 30, 30, 255]) };
+		} else if x == 9 && (10..30).contains(&y) {
+			*pixel = Rgba([115, 115, 35, 255]);
+		}
+	}
+	image::DynamicImage::ImageRgba8(photo.clone()).to_rgb8().save(dir.join("box.jpg")).unwrap();
+	
+	let flood = remove_background(&photo, &ImportOptions::default());
+	assert_eq!(flood.get_pixel(0, 0)[3], 0);
+	assert_eq!(*flood.get_pixel(20, 15), Rgba([200, 30, 30, 255]));
+	assert_eq!(flood.get_pixel(30, 20)[3], 255, "the hole is not connected to the border");
+	
+	let edge = flood.get_pixel(9, 20);
+	assert!(edge[3] > 0 && edge[3] < 255);
+	assert!(edge[1] < 100, "the green was not unmixed from the edge: {:?}", edge);
+	
+	let keyed = remove_background(&photo, &ImportOptions { removal: BackgroundRemoval::ChromaKey, ..Default::default() });
+	assert_eq!(keyed.get_pixel(30, 20)[3], 0);
+	
+	let options = ImportOptions { object_type: 4, type_name: Some("box".to_string()), ground_width: 0.8, ..Default::default() };
+	let path = import_object(dir.join("box.jpg"), &objects, &options).unwrap();
+	assert_eq!(path, objects.join("box.png"));
+	assert!(import_object(dir.join("box.jpg"), &objects, &options).is_err());
+	
+	let details = ObjectDetailsFile::load(&objects).unwrap();
+	assert_eq!(details.object_images["box.png"], ObjectDetails { ground_width: 0.8, object_type: 4, attributes: Attributes::new(), views: vec![] });
+	assert_eq!(details.object_types[&4].name, "box");
+	assert_eq!(crate::objects::validate::lint(&objects).unwrap(), vec![]);
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
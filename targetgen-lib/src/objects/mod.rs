@@ -1,19 +1,37 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use image::{DynamicImage};
-use log::{warn};
+use log::{debug, warn};
 use rand::prelude::SliceRandom;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use crate::generator::coco::{CocoCategory, CocoCategoryInfo};
-use crate::generator::config::TargetGeneratorConfig;
+use crate::generator::config::{ObjectSampling, TargetGeneratorConfig};
 use crate::generator::error::GenerationError;
+use crate::generator::placement::Attributes;
 use crate::generator::util;
+use crate::objects::recolor::RecolorOptions;
+
+pub mod validate;
+pub mod folders;
+pub mod import;
+pub mod recolor;
+
+/// The file that describes the sprites of an object library
+pub const OBJECTS_FILE: &str = "objects.json";
+
+/// The camera elevation in degrees of a sprite that is seen from straight above
+pub const NADIR: f32 = 90.0;
+
+/// The id of objects that are drawn for each image instead of loaded from a sprite, sprite ids start at 1
+pub const PROCEDURAL_OBJECT_ID: u16 = 0;
 
 #[derive(Debug)]
 pub struct ObjectManager {
 	path_buf: PathBuf,
 	objects: Vec<Object>,
-	object_set: HashSet<(u32, String)>
+	object_set: HashSet<(u32, String)>,
+	object_types: HashMap<u32, ObjectType>,
 }
 
 impl ObjectManager {
@@ -21,54 +39,72 @@ impl ObjectManager {
 		ObjectManager {
 			path_buf: path.as_ref().to_path_buf(),
 			objects: vec![],
-			object_set: HashSet::new()
+			object_set: HashSet::new(),
+			object_types: HashMap::new(),
 		}
 	}
 	
 	/// Load training objects into the buffer
 	pub fn load_objects(&mut self) -> Result<(), GenerationError> {
-		let entries = std::fs::read_dir(&self.path_buf)?;
+		// sorted so that object ids are the same between runs
+		let sprites = sprite_files(&self.path_buf)?;
 		
 		// retrieve objects.json file that holds all info about our training objects
-		let out = self.path_buf.join("objects.json");
-		let file = std::fs::read_to_string(&out).ok().ok_or(GenerationError::MissingObjectsJSON)?;
-		
-		let object_details_file: ObjectDetailsFile = serde_json::from_str(&file)?;
+		let object_details_file = ObjectDetailsFile::load(&self.path_buf)?;
+		self.object_types = object_details_file.object_types.clone();
 		
 		let mut id = 1;
 		
-		for entry in entries {
-			let entry = entry?;
-			let path = entry.path();
+		for file_name in &sprites {
+			let path = self.path_buf.join(file_name);
+			let file_name = file_name.as_str();
 			
-			if path.is_dir() || !util::is_image_type(&path.as_os_str().to_str().ok_or(GenerationError::GenericError("Failed to convert path to string".to_string()))?) {
-				continue;
-			}
-			
-			let file_name = if let Some(file_name) = path.file_name() {
-				file_name.to_str().unwrap()
+			let object_details = if let Some(object_details) = object_details_file.object_images.get(file_name) {
+				object_details
 			} else {
-				warn!("Failed to get file name for object: {}", path.display());
+				warn!("No object details found for object: {}", path.display());
 				continue;
 			};
 			
-			let object_details = object_details_file.object_images.get(file_name);
+			self.object_set.insert((object_details.object_type, file_name.to_string()));
 			
-			if object_details.is_none() {
-				warn!("No object details found for object: {}", path.display());
-				continue;
+			// crop away transparent margins so that the bbox is tight and ground_width applies to the object itself
+			let (dynamic_image, padding) = trim_transparent(image::open(&path)?);
+			if padding != Padding::default() {
+				debug!("Trimmed {} of transparent padding from {}", padding, file_name);
 			}
 			
-			let object_details = object_details.unwrap();
-			
-			self.object_set.insert((object_details.object_type, file_name.to_string()));
+			// the sprite itself is the view from straight above facing yaw 0
+			let mut views = vec![];
+			if !object_details.views.is_empty() {
+				views.push(ObjectView {
+					file_name: file_name.to_string(),
+					yaw: 0.0,
+					elevation: NADIR,
+					dynamic_image: dynamic_image.clone(),
+					object_width_meters: object_details.ground_width,
+				});
+			}
+			for view in &object_details.views {
+				views.push(ObjectView {
+					file_name: view.file_name.clone(),
+					yaw: view.yaw,
+					elevation: view.elevation,
+					dynamic_image: trim_transparent(image::open(self.path_buf.join(&view.file_name))?).0,
+					object_width_meters: view.ground_width.unwrap_or(object_details.ground_width),
+				});
+			}
 			
-			let dynamic_image = image::open(path)?;
 			self.objects.push(Object {
 				object_class: object_details.object_type,
 				id,
+				file_name: file_name.to_string(),
 				dynamic_image,
 				object_width_meters: object_details.ground_width,
+				views,
+				attributes: self.attributes(object_details),
+				recolor: self.object_types.get(&object_details.object_type).and_then(|t| t.recolor.clone()),
+				padding,
 			});
 			
 			id += 1;
@@ -77,51 +113,259 @@ impl ObjectManager {
 		Ok(())
 	}
 	
+	/// The attributes of an object, those of its type overridden by its own
+	fn attributes(&self, details: &ObjectDetails) -> Attributes {
+		let mut attributes = self.object_types.get(&details.object_type).map(|t| t.attributes.clone()).unwrap_or_default();
+		attributes.extend(details.attributes.clone());
+		
+		attributes
+	}
+	
+	/// The files that describe the objects, objects.json or the class.json files of a folder-per-class library
+	pub fn details_paths(&self) -> Result<Vec<PathBuf>, GenerationError> {
+		let objects_json = self.path_buf.join(OBJECTS_FILE);
+		if objects_json.is_file() {
+			return Ok(vec![objects_json]);
+		}
+		
+		Ok(folders::class_folders(&self.path_buf)?.into_iter().map(|folder| folder.join(folders::CLASS_FILE)).filter(|path| path.is_file()).collect())
+	}
+	
+	/// The files of every loaded object sprite
+	pub fn sprite_paths(&self) -> Vec<PathBuf> {
+		self.objects.iter()
+			.flat_map(|object| std::iter::once(&object.file_name).chain(object.views.iter().skip(1).map(|view| &view.file_name)))
+			.map(|file_name| self.path_buf.join(file_name))
+			.collect()
+	}
+	
+	/// The transparent padding that was trimmed from each sprite when it was loaded, by file name
+	pub fn trimmed_padding(&self) -> Vec<(&str, Padding)> {
+		self.objects.iter().map(|object| (object.file_name.as_str(), object.padding)).collect()
+	}
+	
+	/// Move the class id of every object up by [offset], ex. so that the classes of a second library
+	/// do not share ids with the first
+	pub fn offset_classes(&mut self, offset: u32) {
+		for object in &mut self.objects {
+			object.object_class += offset;
+		}
+		self.object_set = self.object_set.drain().map(|(object_type, file_name)| (object_type + offset, file_name)).collect();
+		self.object_types = self.object_types.drain().map(|(object_type, details)| (object_type + offset, details)).collect();
+	}
+	
 	/// Generate a set of training objects a random that could be used to generate a target
 	/// [amount] is the maximum number of objects to return
 	/// Returns a set of objects that will contain no duplicates
-	pub fn generate_set(&self, amount: u32, config: &TargetGeneratorConfig) -> Result<Vec<&Object>, GenerationError> {
-		let mut rng = rand::thread_rng();
+	pub fn generate_set<R: Rng + ?Sized>(&self, amount: u32, config: &TargetGeneratorConfig, rng: &mut R) -> Result<Vec<&Object>, GenerationError> {
 		let mut set = Vec::new();
 		
+		if config.object_sampling != ObjectSampling::Sprite {
+			let weights = self.class_weights();
+			let mut classes = vec![];
+			
+			for _ in 0..amount {
+				let (class, _) = weights.choose_weighted(rng, |(_, weight)| *weight)
+					.map_err(|e| GenerationError::GenericError(format!("Invalid class weights: {}", e)))?;
+				classes.push(*class);
+			}
+			
+			return self.generate_set_for_classes(&classes, config, rng);
+		}
+		
 		if !config.permit_duplicates {
 			if amount > self.objects.len() as u32 {
 				return Err(GenerationError::NotEnoughObjectsAvailable);
 			}
 
-			self.objects.choose_multiple(&mut rng, amount as usize).for_each(|object| {
+			self.objects.choose_multiple(rng, amount as usize).for_each(|object| {
 				set.push(object);
 			});
 		} else {
 			for _ in 0..amount {
-				set.push(self.objects.choose(&mut rng).unwrap());
+				set.push(self.objects.choose(rng).unwrap());
 			}
 		}
 		
 		Ok(set)
 	}
+	
+	/// Generate a set of [amount] objects that are chosen independently of each other, so the same object
+	/// may be chosen more than once and a library of only a few objects can fill any amount
+	pub fn generate_set_with_replacement<R: Rng + ?Sized>(&self, amount: u32, rng: &mut R) -> Vec<&Object> {
+		(0..amount).filter_map(|_| self.objects.choose(rng)).collect()
+	}
+	
+	/// Generate a set of objects with one object of each of the given classes. If there are not enough
+	/// different objects of a class to avoid duplicates, an unused object of another class is used.
+	pub fn generate_set_for_classes<R: Rng + ?Sized>(&self, classes: &[u32], config: &TargetGeneratorConfig, rng: &mut R) -> Result<Vec<&Object>, GenerationError> {
+		if !config.permit_duplicates && classes.len() > self.objects.len() {
+			return Err(GenerationError::NotEnoughObjectsAvailable);
+		}
+		
+		let mut set: Vec<&Object> = Vec::new();
+		
+		for class in classes {
+			let unused = |object: &&Object| config.permit_duplicates || !set.contains(object);
+			let candidates = self.objects.iter().filter(|o| o.object_class == *class).filter(unused).collect::<Vec<&Object>>();
+			
+			let object = if let Some(object) = candidates.choose(rng) {
+				*object
+			} else {
+				*self.objects.iter().filter(unused).collect::<Vec<&Object>>().choose(rng).ok_or(GenerationError::NotEnoughObjectsAvailable)?
+			};
+			
+			set.push(object);
+		}
+		
+		Ok(set)
+	}
+	
+	/// The sampling weight of every class that has at least one loaded object, sorted by class.
+	/// Classes without a weight in objects.json have a weight of 1.
+	pub fn class_weights(&self) -> Vec<(u32, f32)> {
+		let mut classes = self.objects.iter().map(|o| o.object_class).collect::<Vec<u32>>();
+		classes.sort();
+		classes.dedup();
+		
+		classes.into_iter().map(|class| (class, self.object_types.get(&class).and_then(|t| t.weight).unwrap_or(1.0))).collect()
+	}
+	
+	/// Plan the classes of [total] objects so that the number of objects of each class matches the
+	/// class weights as closely as possible, in a random order
+	pub fn balanced_schedule<R: Rng + ?Sized>(&self, total: usize, rng: &mut R) -> Vec<u32> {
+		let weights = self.class_weights();
+		let sum: f32 = weights.iter().map(|(_, weight)| weight.max(0.0)).sum();
+		
+		if sum <= 0.0 {
+			return vec![];
+		}
+		
+		// largest remainder, so that the counts add up to exactly the total
+		let exact = weights.iter().map(|(_, weight)| weight.max(0.0) as f64 / sum as f64 * total as f64).collect::<Vec<f64>>();
+		let mut counts = exact.iter().map(|e| e.floor() as usize).collect::<Vec<usize>>();
+		let mut by_remainder = (0..weights.len()).collect::<Vec<usize>>();
+		by_remainder.sort_by(|a, b| (exact[*b] - exact[*b].floor()).total_cmp(&(exact[*a] - exact[*a].floor())).then(a.cmp(b)));
+		
+		for i in by_remainder.into_iter().take(total - counts.iter().sum::<usize>()) {
+			counts[i] += 1;
+		}
+		
+		let mut schedule = weights.iter().zip(counts).flat_map(|((class, _), count)| std::iter::repeat_n(*class, count)).collect::<Vec<u32>>();
+		schedule.shuffle(rng);
+		
+		schedule
+	}
 }
 
 impl CocoCategoryInfo for ObjectManager {
+	/// One category per object type that has at least one loaded object, named after the type
 	fn categories(&self) -> Vec<CocoCategory> {
-		let mut categories = vec![];
-		
-		for (object_type, name) in &self.object_set {
-			categories.push(CocoCategory::new(*object_type, name.clone()));
-		}
+		let mut types = self.object_set.iter().map(|(object_type, _)| *object_type).collect::<Vec<u32>>();
+		types.sort();
+		types.dedup();
 		
-		categories
+		types.into_iter().map(|object_type| {
+			let name = self.object_types.get(&object_type).map(|t| t.name.clone()).unwrap_or_else(|| object_type.to_string());
+			CocoCategory::new(object_type, name)
+		}).collect()
 	}
 }
 
 #[derive(Debug, Clone)]
 pub struct Object {
 	pub(crate) object_class: u32,
-	id: u16,
+	pub(crate) id: u16,
+	/// the file name of the sprite the object was loaded from
+	pub(crate) file_name: String,
+	pub(crate) dynamic_image: DynamicImage,
+	pub(crate) object_width_meters: f32,
+	/// the annotation attributes of the object
+	pub(crate) attributes: Attributes,
+	/// how the object may be recolored when it is placed, from its type
+	pub(crate) recolor: Option<RecolorOptions>,
+	/// the images of the object from different headings and camera angles, starting with the sprite
+	/// itself. Empty if the object only has the one sprite.
+	pub(crate) views: Vec<ObjectView>,
+	/// the transparent margins that were cropped from the sprite
+	pub(crate) padding: Padding,
+}
+
+impl Object {
+	/// The view that is closest to seeing the object facing [heading] from a camera [elevation] degrees
+	/// above the horizon, along with its index. None if the object only has the one sprite.
+	pub(crate) fn closest_view(&self, heading: f32, elevation: f32) -> Option<(usize, &ObjectView)> {
+		let distance = |view: &ObjectView| {
+			let yaw = (heading - view.yaw).rem_euclid(360.0);
+			yaw.min(360.0 - yaw) + (elevation - view.elevation).abs()
+		};
+		
+		self.views.iter().enumerate().min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+	}
+}
+
+/// A single image of a multi-view object
+#[derive(Debug, Clone)]
+pub struct ObjectView {
+	pub(crate) file_name: String,
+	/// the heading the object faces in the image, in degrees clockwise
+	pub(crate) yaw: f32,
+	/// the angle of the camera above the horizon in degrees
+	pub(crate) elevation: f32,
 	pub(crate) dynamic_image: DynamicImage,
 	pub(crate) object_width_meters: f32,
 }
 
+/// The number of pixels cropped from each side of a sprite
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Padding {
+	pub left: u32,
+	pub top: u32,
+	pub right: u32,
+	pub bottom: u32,
+}
+
+impl std::fmt::Display for Padding {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}px left, {}px top, {}px right, {}px bottom", self.left, self.top, self.right, self.bottom)
+	}
+}
+
+/// Crop an image to the bounding box of its pixels that are not fully transparent. Images that are
+/// fully transparent are returned as is.
+pub fn trim_transparent(image: DynamicImage) -> (DynamicImage, Padding) {
+	if !image.color().has_alpha() {
+		return (image, Padding::default());
+	}
+	
+	let rgba = image.to_rgba8();
+	let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+	
+	for (x, y, pixel) in rgba.enumerate_pixels() {
+		if pixel[3] > 0 {
+			(left, top) = (left.min(x), top.min(y));
+			(right, bottom) = (right.max(x + 1), bottom.max(y + 1));
+		}
+	}
+	
+	if left == u32::MAX {
+		return (image, Padding::default());
+	}
+	
+	let padding = Padding {
+		left,
+		top,
+		right: image.width() - right,
+		bottom: image.height() - bottom,
+	};
+	
+	if padding == Padding::default() {
+		return (image, padding);
+	}
+	
+	(image.crop_imm(left, top, right - left, bottom - top), padding)
+}
+
 impl PartialEq for Object {
 	fn eq(&self, other: &Self) -> bool {
 		self.object_class == other.object_class && self.id == other.id
@@ -144,16 +388,139 @@ pub struct ObjectDetailsFile {
 	object_types: HashMap<u32, ObjectType>
 }
 
+impl ObjectDetailsFile {
+	/// Read the objects.json file of an object library folder. Without one, a library of class folders is
+	/// described as if it had one, see [folders::from_folders].
+	pub fn load<P: AsRef<Path>>(objects_path: P) -> Result<Self, GenerationError> {
+		let objects_path = objects_path.as_ref();
+		let file = match std::fs::read_to_string(objects_path.join(OBJECTS_FILE)) {
+			Ok(file) => file,
+			Err(_) if !folders::class_folders(objects_path)?.is_empty() => return folders::from_folders(objects_path),
+			Err(_) => return Err(GenerationError::MissingObjectsJSON),
+		};
+		
+		Ok(serde_json::from_str(&file)?)
+	}
+	
+	/// Write the objects.json file of an object library folder, sorted so that changes diff cleanly
+	pub fn save<P: AsRef<Path>>(&self, objects_path: P) -> Result<(), GenerationError> {
+		#[derive(Serialize)]
+		struct Sorted<'a> {
+			object_images: BTreeMap<&'a String, &'a ObjectDetails>,
+			object_types: BTreeMap<&'a u32, &'a ObjectType>,
+		}
+		
+		let sorted = Sorted {
+			object_images: self.object_images.iter().collect(),
+			object_types: self.object_types.iter().collect(),
+		};
+		std::fs::write(objects_path.as_ref().join(OBJECTS_FILE), serde_json::to_string_pretty(&sorted)?)?;
+		
+		Ok(())
+	}
+}
+
+/// The image files of an object library relative to it and sorted, both at the top level and inside class folders
+pub(crate) fn sprite_files(objects_path: &Path) -> Result<Vec<String>, GenerationError> {
+	let mut sprites = vec![];
+	
+	for entry in std::fs::read_dir(objects_path)? {
+		let path = entry?.path();
+		
+		if path.is_dir() {
+			for entry in std::fs::read_dir(&path)? {
+				let sprite = entry?.path();
+				if sprite.is_file() && util::is_image_type(&sprite.to_string_lossy()) {
+					sprites.push(format!("{}/{}", path.file_name().unwrap().to_string_lossy(), sprite.file_name().unwrap().to_string_lossy()));
+				}
+			}
+		} else if util::is_image_type(&path.to_string_lossy()) {
+			sprites.push(path.file_name().unwrap().to_string_lossy().to_string());
+		}
+	}
+	sprites.sort();
+	
+	Ok(sprites)
+}
+
 /// All details about a training object
-#[derive(Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ObjectDetails {
 	ground_width: f32,
 	object_type: u32,
+	/// written to every annotation of the object, overriding the attributes of its type
+	#[serde(default, skip_serializing_if = "Attributes::is_empty")]
+	attributes: Attributes,
+	/// more images of the same object from other headings and camera angles, the sprite itself is
+	/// the view facing yaw 0 from straight above
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	views: Vec<ViewDetails>,
+}
+
+/// Another image of an object in objects.json
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ViewDetails {
+	file_name: String,
+	/// the heading the object faces in the image, in degrees clockwise
+	#[serde(default)]
+	yaw: f32,
+	/// the angle of the camera above the horizon in degrees, 90 is straight down
+	#[serde(default = "nadir")]
+	elevation: f32,
+	/// the ground width of the object as it appears in this view, defaults to the ground width of the object
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	ground_width: Option<f32>,
+}
+
+fn nadir() -> f32 {
+	NADIR
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ObjectType {
 	name: String,
+	/// how likely the class is to be chosen relative to the other classes, defaults to 1
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	weight: Option<f32>,
+	/// written to every annotation of an object of this type
+	#[serde(default, skip_serializing_if = "Attributes::is_empty")]
+	attributes: Attributes,
+	/// synthesize recolored variants of the sprites of this type, the chosen color is written to the `color` attribute
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	recolor: Option<RecolorOptions>,
+}
+
+#[test]
+fn test_balanced_run() {
+	use crate::generator::coco::CocoFormatFile;
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("balanced_run");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	// tire is three times as likely as car
+	let details = std::fs::read_to_string(objects.join("objects.json")).unwrap();
+	std::fs::write(objects.join("objects.json"), details.replace(r#"{ "name": "tire" }"#, r#"{ "name": "tire", "weight": 3.0 }"#)).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	assert_eq!(tg.object_manager.class_weights(), vec![(1, 3.0), (7, 1.0)]);
+	assert_eq!(tg.object_manager.balanced_schedule(10, &mut rand::thread_rng()).iter().filter(|c| **c == 1).count(), 8);
+	
+	tg.config.permit_duplicates = true;
+	tg.config.permit_collisions = true;
+	tg.config.object_sampling = ObjectSampling::BalancedRun;
+	tg.generate_targets(20, ..4, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	let tires = file.annotations().iter().filter(|a| a.category_id() == 1).count();
+	let total = file.annotations().len();
+	assert_eq!(tires, (total as f32 * 0.75).round() as usize);
+	
+	std::fs::remove_dir_all(dir).unwrap();
 }
 
 // Used to generate the starting object mapping file
@@ -163,27 +530,41 @@ fn generate_objects_json_file() {
 	let mut object_images = HashMap::new();
 	object_images.insert("bicycle_1.png".to_string(), ObjectDetails {
 		ground_width: 1.73,
-		object_type: 0
+		object_type: 0,
+		attributes: Attributes::new(),
+		views: vec![],
 	});
 	object_images.insert("bicycle_2.png".to_string(), ObjectDetails {
 		ground_width: 1.73,
-		object_type: 0
+		object_type: 0,
+		attributes: Attributes::new(),
+		views: vec![],
 	});
 	object_images.insert("tire_1.png".to_string(), ObjectDetails {
 		ground_width: 1.0,
-		object_type: 1
+		object_type: 1,
+		attributes: Attributes::new(),
+		views: vec![],
 	});
 	object_images.insert("tire_2.png".to_string(), ObjectDetails {
 		ground_width: 1.0,
-		object_type: 1
+		object_type: 1,
+		attributes: Attributes::new(),
+		views: vec![],
 	});
 	
 	let mut object_types = HashMap::new();
 	object_types.insert(0, ObjectType {
-		name: "bicycle".to_string()
+		name: "bicycle".to_string(),
+		weight: None,
+		attributes: Attributes::new(),
+		recolor: None,
 	});
 	object_types.insert(1, ObjectType {
-		name: "tire".to_string()
+		name: "tire".to_string(),
+		weight: None,
+		attributes: Attributes::new(),
+		recolor: None,
 	});
 	
 	let object_details_file = ObjectDetailsFile {
@@ -194,4 +575,127 @@ fn generate_objects_json_file() {
 	let json = serde_json::to_string_pretty(&object_details_file).unwrap();
 	
 	std::fs::write("objects/objects.json", json).unwrap();
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_trim_transparent_padding() {
+	use image::{Rgba, RgbaImage};
+	use crate::generator::util;
+	
+	let dir = util::test_dir("trim_transparent_padding");
+	let (_, objects) = util::create_test_assets(&dir);
+	
+	// a 20x10 car in the middle of a transparent 40x30 canvas
+	let mut padded = RgbaImage::new(40, 30);
+	for y in 12..22 {
+		for x in 5..25 {
+			padded.put_pixel(x, y, Rgba([200, 30, 30, 255]));
+		}
+	}
+	padded.save(objects.join("car_1.png")).unwrap();
+	
+	let mut manager = ObjectManager::new(&objects);
+	manager.load_objects().unwrap();
+	
+	let car = manager.objects.iter().find(|o| o.file_name == "car_1.png").unwrap();
+	assert_eq!((car.dynamic_image.width(), car.dynamic_image.height()), (20, 10));
+	assert!(car.dynamic_image.to_rgba8().pixels().all(|p| p[3] == 255));
+	
+	assert_eq!(manager.trimmed_padding(), vec![
+		("car_1.png", Padding { left: 5, top: 12, right: 15, bottom: 8 }),
+		("tire_1.png", Padding::default()),
+	]);
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_object_attributes() {
+	use crate::generator::coco::CocoFormatFile;
+	use crate::generator::TargetGenerator;
+	
+	let dir = util::test_dir("object_attributes");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	// the car overrides the color of its type
+	let details = std::fs::read_to_string(objects.join("objects.json")).unwrap()
+		.replace(r#""object_type": 7 }"#, r#""object_type": 7, "attributes": { "color": "red" } }"#)
+		.replace(r#"{ "name": "car" }"#, r#"{ "name": "car", "attributes": { "color": "white", "doors": 4 } }"#);
+	std::fs::write(objects.join("objects.json"), details).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.permit_collisions = true;
+	tg.generate_targets(4, ..3, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	assert!(!file.annotations().is_empty());
+	for annotation in file.annotations() {
+		if annotation.category_id() == 7 {
+			assert_eq!(annotation.attributes(), &Attributes::from([("color".to_string(), "red".into()), ("doors".to_string(), 4.into())]));
+		} else {
+			assert!(annotation.attributes().is_empty());
+		}
+	}
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_multi_view_objects() {
+	use image::{Rgba, RgbaImage};
+	use crate::generator::TargetGenerator;
+	
+	let dir = util::test_dir("multi_view_objects");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	RgbaImage::from_pixel(40, 15, Rgba([200, 30, 30, 255])).save(objects.join("car_side.png")).unwrap();
+	RgbaImage::from_pixel(20, 40, Rgba([200, 30, 30, 255])).save(objects.join("car_back.png")).unwrap();
+	let details = std::fs::read_to_string(objects.join("objects.json")).unwrap().replace(r#""object_type": 7 }"#, r#""object_type": 7, "views": [
+		{ "file_name": "car_side.png", "yaw": 90, "elevation": 30, "ground_width": 4.0 },
+		{ "file_name": "car_back.png", "yaw": 180 }
+	] }"#);
+	std::fs::write(objects.join("objects.json"), details).unwrap();
+	assert_eq!(validate::lint(&objects).unwrap(), vec![]);
+	
+	let mut manager = ObjectManager::new(&objects);
+	manager.load_objects().unwrap();
+	assert_eq!(manager.objects.len(), 2);
+	assert_eq!(manager.sprite_paths().len(), 4);
+	
+	let car = manager.objects.iter().find(|o| o.object_class == 7).unwrap();
+	let closest = |heading, elevation| car.closest_view(heading, elevation).map(|(_, view)| view.file_name.as_str());
+	assert_eq!(closest(10.0, NADIR), Some("car_1.png"));
+	assert_eq!(closest(350.0, NADIR), Some("car_1.png"));
+	assert_eq!(closest(170.0, NADIR), Some("car_back.png"));
+	assert_eq!(closest(80.0, 35.0), Some("car_side.png"));
+	assert_eq!(car.views[1].object_width_meters, 4.0);
+	
+	// the chosen view is recorded and only the rest of the heading is rotated
+	let mut tg = TargetGenerator::new(backgrounds, objects, dir.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.permit_collisions = true;
+	tg.config.min_camera_elevation = 20.0;
+	for seed in 0..5 {
+		let target = tg.generate_target(util::STANDARD_PPM, 3, seed).unwrap();
+		assert!(target.provenance.camera_elevation.is_some_and(|e| (20.0..=NADIR).contains(&e)));
+		
+		for object in target.provenance.objects.iter().filter(|o| o.object_class == 7) {
+			let view = object.augmentations.iter().find(|a| a.name == "select_view").unwrap();
+			assert_eq!(view.parameters["sprite"], object.sprite.as_str());
+			
+			let residual = util::residual_rotation(view.parameters["heading"].as_i64().unwrap() as i32, view.parameters["yaw"].as_f64().unwrap().round() as i32);
+			assert_eq!(object.rotation, residual as u32);
+		}
+	}
+	
+	// the rest of the heading is rounded to the nearest quarter turn, also across 360
+	assert_eq!(util::residual_rotation(355, 0), 0);
+	assert_eq!(util::residual_rotation(5, 350), 0);
+	assert_eq!(util::residual_rotation(130, 0), 90);
+	assert_eq!(util::residual_rotation(140, 0), 180);
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use image::{Rgba, RgbaImage};
+use rand::Rng;
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+
+/// The saturation below which a pixel counts as gray rather than colored
+const MIN_SATURATION: f32 = 0.25;
+/// The brightness below which a pixel is too dark to have a color, ex. tires and shadows
+const MIN_VALUE: f32 = 0.15;
+/// The brightness above which a gray pixel counts as a light body color, ex. a white car
+const MIN_LIGHT_VALUE: f32 = 0.5;
+
+/// Per-class option in objects.json to synthesize recolored variants of the sprites of a class.
+/// Only the region of the dominant color is changed, shading within it is preserved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecolorOptions {
+	/// how likely an object of the class is to be recolored each time it is placed
+	#[serde(default = "default_probability")]
+	pub probability: f32,
+	/// the colors to choose from by name, defaults to common vehicle colors
+	#[serde(default = "default_colors")]
+	pub colors: BTreeMap<String, [u8; 3]>,
+	/// how far in degrees the hue of a pixel may be from the dominant hue to be recolored
+	#[serde(default = "default_hue_tolerance")]
+	pub hue_tolerance: f32,
+}
+
+fn default_probability() -> f32 {
+	0.5
+}
+
+fn default_colors() -> BTreeMap<String, [u8; 3]> {
+	BTreeMap::from([
+		("white".to_string(), [235, 235, 235]),
+		("black".to_string(), [25, 25, 25]),
+		("gray".to_string(), [120, 120, 120]),
+		("silver".to_string(), [180, 182, 185]),
+		("red".to_string(), [180, 25, 25]),
+		("blue".to_string(), [30, 60, 170]),
+		("green".to_string(), [40, 120, 50]),
+		("yellow".to_string(), [230, 190, 30]),
+	])
+}
+
+fn default_hue_tolerance() -> f32 {
+	25.0
+}
+
+impl Default for RecolorOptions {
+	fn default() -> Self {
+		Self {
+			probability: default_probability(),
+			colors: default_colors(),
+			hue_tolerance: default_hue_tolerance(),
+		}
+	}
+}
+
+impl RecolorOptions {
+	/// Maybe pick a color to recolor an object with, returns its name and value
+	pub fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<(&str, [u8; 3])> {
+		if !rng.gen_bool(self.probability.clamp(0.0, 1.0) as f64) {
+			return None;
+		}
+		
+		self.colors.iter().choose(rng).map(|(name, rgb)| (name.as_str(), *rgb))
+	}
+}
+
+/// The region of a sprite that holds its dominant color
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Region {
+	/// colored pixels around this hue in degrees
+	Hue(f32),
+	/// light gray pixels
+	Light,
+}
+
+impl Region {
+	fn contains(&self, (h, s, v): (f32, f32, f32), hue_tolerance: f32) -> bool {
+		match self {
+			Region::Hue(hue) => s >= MIN_SATURATION && v >= MIN_VALUE && hue_distance(h, *hue) <= hue_tolerance,
+			Region::Light => s < MIN_SATURATION && v >= MIN_LIGHT_VALUE,
+		}
+	}
+}
+
+/// Find the dominant color region of a sprite among its opaque pixels. Dark pixels never count.
+fn dominant_region(image: &RgbaImage, hue_tolerance: f32) -> Option<Region> {
+	let mut hues = vec![];
+	let mut bins = [0u32; 36];
+	let mut light = 0;
+	
+	for pixel in image.pixels().filter(|p| p[3] > 127) {
+		let (h, s, v) = rgb_to_hsv(pixel);
+		if s >= MIN_SATURATION && v >= MIN_VALUE {
+			hues.push(h);
+			bins[(h / 10.0) as usize % 36] += 1;
+		} else if s < MIN_SATURATION && v >= MIN_LIGHT_VALUE {
+			light += 1;
+		}
+	}
+	
+	// the most common hue, averaged with the hues around it so that a color split across bins is not missed
+	let peak = (0..36).max_by_key(|bin| (bins[*bin], std::cmp::Reverse(*bin)))? as f32 * 10.0 + 5.0;
+	let near = hues.iter().filter(|h| hue_distance(**h, peak) <= hue_tolerance).collect::<Vec<_>>();
+	let (sin, cos) = near.iter().fold((0.0, 0.0), |(sin, cos), h| (sin + h.to_radians().sin(), cos + h.to_radians().cos()));
+	
+	if near.is_empty() && light == 0 {
+		None
+	} else if near.len() >= light {
+		Some(Region::Hue(f32::atan2(sin, cos).to_degrees().rem_euclid(360.0)))
+	} else {
+		Some(Region::Light)
+	}
+}
+
+/// Swap the dominant color of a sprite for [color]. Saturation and brightness of each pixel are scaled
+/// by how far the region is from the new color so that the shading of the sprite is kept.
+/// Returns None if the sprite has no dominant color, ex. it is completely dark.
+pub fn recolor(image: &RgbaImage, color: [u8; 3], hue_tolerance: f32) -> Option<RgbaImage> {
+	let region = dominant_region(image, hue_tolerance)?;
+	
+	let pixels = image.pixels().filter(|p| p[3] > 127).map(rgb_to_hsv).filter(|hsv| region.contains(*hsv, hue_tolerance)).collect::<Vec<_>>();
+	let mean_s = pixels.iter().map(|(_, s, _)| s).sum::<f32>() / pixels.len() as f32;
+	let mean_v = pixels.iter().map(|(_, _, v)| v).sum::<f32>() / pixels.len() as f32;
+	let (target_h, target_s, target_v) = rgb_to_hsv(&Rgba([color[0], color[1], color[2], 255]));
+	
+	let mut recolored = image.clone();
+	for pixel in recolored.pixels_mut() {
+		let hsv = rgb_to_hsv(pixel);
+		if pixel[3] == 0 || !region.contains(hsv, hue_tolerance) {
+			continue;
+		}
+		
+		let (_, s, v) = hsv;
+		// a gray region has no saturation to scale, so the new color is used as is
+		let s = if mean_s < 0.05 { target_s } else { (s * target_s / mean_s).clamp(0.0, 1.0) };
+		let v = (v * target_v / mean_v.max(f32::EPSILON)).clamp(0.0, 1.0);
+		let [r, g, b] = hsv_to_rgb(target_h, s, v);
+		*pixel = Rgba([r, g, b, pixel[3]]);
+	}
+	
+	Some(recolored)
+}
+
+/// The smallest difference between two hues in degrees
+fn hue_distance(a: f32, b: f32) -> f32 {
+	let d = (a - b).abs() % 360.0;
+	d.min(360.0 - d)
+}
+
+/// Hue in degrees, saturation and value between 0 and 1
+fn rgb_to_hsv(pixel: &Rgba<u8>) -> (f32, f32, f32) {
+	let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0);
+	let max = r.max(g).max(b);
+	let min = r.min(g).min(b);
+	let delta = max - min;
+	
+	let h = if delta == 0.0 {
+		0.0
+	} else if max == r {
+		60.0 * ((g - b) / delta).rem_euclid(6.0)
+	} else if max == g {
+		60.0 * ((b - r) / delta + 2.0)
+	} else {
+		60.0 * ((r - g) / delta + 4.0)
+	};
+	let s = if max == 0.0 { 0.0 } else { delta / max };
+	
+	(h, s, max)
+}
+
+fn hsv_to_rgb(h: f32, s: f32, v: f32) -> [u8; 3] {
+	let c = v * s;
+	let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+	let m = v - c;
+	let (r, g, b) = match (h.rem_euclid(360.0) / 60.0) as u32 {
+		0 => (c, x, 0.0),
+		1 => (x, c, 0.0),
+		2 => (0.0, c, x),
+		3 => (0.0, x, c),
+		4 => (x, 0.0, c),
+		_ => (c, 0.0, x),
+	};
+	
+	[r, g, b].map(|channel| ((channel + m) * 255.0).round() as u8)
+}
+
+#[test]
+fn test_recolor() {
+	// a red body shaded from dark to bright on the left, black tires on the right
+	let mut sprite = RgbaImage::new(20, 10);
+	for x in 0..15 {
+		for y in 0..10 {
+			sprite.put_pixel(x, y, Rgba([120 + x as u8 * 8, 20, 20, 255]));
+		}
+	}
+	for x in 15..20 {
+		for y in 0..10 {
+			sprite.put_pixel(x, y, Rgba([15, 15, 15, 255]));
+		}
+	}
+	
+	assert!(matches!(dominant_region(&sprite, 25.0), Some(Region::Hue(h)) if hue_distance(h, 0.0) < 1.0));
+	
+	let blue = recolor(&sprite, [30, 60, 170], 25.0).unwrap();
+	for x in 0..15 {
+		let (h, _, _) = rgb_to_hsv(blue.get_pixel(x, 5));
+		assert!((h - 225.0).abs() < 5.0, "hue {} at {}", h, x);
+	}
+	// shading is kept and the tires are untouched
+	assert!(rgb_to_hsv(blue.get_pixel(0, 5)).2 < rgb_to_hsv(blue.get_pixel(14, 5)).2);
+	assert_eq!(blue.get_pixel(17, 5), &Rgba([15, 15, 15, 255]));
+	
+	// a white car becomes red, a completely dark sprite has nothing to recolor
+	let white = RgbaImage::from_pixel(10, 10, Rgba([230, 230, 230, 255]));
+	let (h, s, _) = rgb_to_hsv(recolor(&white, [180, 25, 25], 25.0).unwrap().get_pixel(5, 5));
+	assert!(!(10.0..350.0).contains(&h) && s > 0.5);
+	assert_eq!(recolor(&RgbaImage::from_pixel(10, 10, Rgba([10, 10, 10, 255])), [180, 25, 25], 25.0), None);
+	
+	assert_eq!(hsv_to_rgb(0.0, 1.0, 1.0), [255, 0, 0]);
+	assert_eq!(rgb_to_hsv(&Rgba([0, 0, 255, 255])), (240.0, 1.0, 1.0));
+}
+
+#[test]
+fn test_recolor_during_generation() {
+	use crate::generator::coco::CocoFormatFile;
+	use crate::generator::util;
+	use crate::generator::TargetGenerator;
+	
+	let dir = util::test_dir("recolor_during_generation");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	// every red car is painted blue, the black tire has no color to swap
+	let details = std::fs::read_to_string(objects.join("objects.json")).unwrap()
+		.replace(r#"{ "name": "car" }"#, r#"{ "name": "car", "recolor": { "probability": 1.0, "colors": { "blue": [30, 60, 170] } } }"#)
+		.replace(r#"{ "name": "tire" }"#, r#"{ "name": "tire", "recolor": { "probability": 1.0 } }"#);
+	std::fs::write(objects.join("objects.json"), details).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	tg.config.permit_collisions = true;
+	tg.generate_targets(4, ..3, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	assert!(file.annotations().iter().any(|a| a.category_id() == 7));
+	for annotation in file.annotations() {
+		let color = annotation.attributes().get("color").and_then(|c| c.as_str());
+		assert_eq!(color, if annotation.category_id() == 7 { Some("blue") } else { None });
+	}
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use image::{ImageDecoder, ImageReader};
+use crate::generator::error::GenerationError;
+use crate::objects::{sprite_files, ObjectDetailsFile};
+
+/// A problem with an object library that would make objects silently go missing or come out wrong
+#[derive(Debug, Clone, PartialEq)]
+pub enum LintIssue {
+	/// a sprite in the folder that has no entry in objects.json, it is never used
+	MissingDetails { sprite: String },
+	/// an entry in objects.json without a sprite in the folder
+	MissingFile { sprite: String },
+	/// an entry whose object type is not defined in `object_types`
+	UndefinedObjectType { sprite: String, object_type: u32 },
+	NonPositiveGroundWidth { sprite: String, ground_width: f32 },
+	/// a sprite without an alpha channel, its whole rectangle would be pasted onto the background
+	NoAlphaChannel { sprite: String },
+	UnreadableImage { sprite: String, error: String },
+}
+
+impl Display for LintIssue {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			LintIssue::MissingDetails { sprite } => write!(f, "{}: no entry in objects.json", sprite),
+			LintIssue::MissingFile { sprite } => write!(f, "{}: listed in objects.json but the file does not exist", sprite),
+			LintIssue::UndefinedObjectType { sprite, object_type } => write!(f, "{}: object type {} is not defined in object_types", sprite, object_type),
+			LintIssue::NonPositiveGroundWidth { sprite, ground_width } => write!(f, "{}: ground_width must be positive, got {}", sprite, ground_width),
+			LintIssue::NoAlphaChannel { sprite } => write!(f, "{}: the image has no alpha channel", sprite),
+			LintIssue::UnreadableImage { sprite, error } => write!(f, "{}: the image can not be read, {}", sprite, error),
+		}
+	}
+}
+
+/// Check an object library folder against its objects.json. Only fails if objects.json itself can not
+/// be read, every other problem is returned as an issue, sorted by sprite.
+pub fn lint<P: AsRef<Path>>(objects_path: P) -> Result<Vec<LintIssue>, GenerationError> {
+	let folder = objects_path.as_ref();
+	let details = ObjectDetailsFile::load(folder)?;
+	
+	let sprites = sprite_files(folder)?.into_iter().collect::<BTreeSet<_>>();
+	
+	let views = details.object_images.values().flat_map(|d| d.views.iter().map(|view| &view.file_name)).collect::<BTreeSet<_>>();
+	let mut issues = vec![];
+	
+	for sprite in &sprites {
+		if !details.object_images.contains_key(sprite) && !views.contains(sprite) {
+			issues.push(LintIssue::MissingDetails { sprite: sprite.clone() });
+		}
+		
+		let color = ImageReader::open(folder.join(sprite))
+			.and_then(|reader| reader.with_guessed_format())
+			.map_err(|e| e.to_string())
+			.and_then(|reader| reader.into_decoder().map_err(|e| e.to_string()))
+			.map(|decoder| decoder.color_type());
+		
+		match color {
+			Ok(color) if !color.has_alpha() => issues.push(LintIssue::NoAlphaChannel { sprite: sprite.clone() }),
+			Err(error) => issues.push(LintIssue::UnreadableImage { sprite: sprite.clone(), error }),
+			_ => {}
+		}
+	}
+	
+	let mut entries = details.object_images.iter().collect::<Vec<_>>();
+	entries.sort_by(|a, b| a.0.cmp(b.0));
+	
+	for (sprite, object_details) in entries {
+		if !sprites.contains(sprite) {
+			issues.push(LintIssue::MissingFile { sprite: sprite.clone() });
+		}
+		
+		for view in object_details.views.iter().filter(|view| !sprites.contains(&view.file_name)) {
+			issues.push(LintIssue::MissingFile { sprite: view.file_name.clone() });
+		}
+		
+		if !details.object_types.contains_key(&object_details.object_type) {
+			issues.push(LintIssue::UndefinedObjectType { sprite: sprite.clone(), object_type: object_details.object_type });
+		}
+		
+		if object_details.ground_width.is_nan() || object_details.ground_width <= 0.0 {
+			issues.push(LintIssue::NonPositiveGroundWidth { sprite: sprite.clone(), ground_width: object_details.ground_width });
+		}
+	}
+	
+	issues.sort_by_key(|issue| issue_sprite(issue).to_string());
+	
+	Ok(issues)
+}
+
+fn issue_sprite(issue: &LintIssue) -> &str {
+	match issue {
+		LintIssue::MissingDetails { sprite } |
+		LintIssue::MissingFile { sprite } |
+		LintIssue::UndefinedObjectType { sprite, .. } |
+		LintIssue::NonPositiveGroundWidth { sprite, .. } |
+		LintIssue::NoAlphaChannel { sprite } |
+		LintIssue::UnreadableImage { sprite, .. } => sprite,
+	}
+}
+
+#[test]
+fn test_lint_objects() {
+	use image::{Rgb, RgbImage};
+	use crate::generator::util;
+	
+	let dir = util::test_dir("lint_objects");
+	let (_, objects) = util::create_test_assets(&dir);
+	assert_eq!(lint(&objects).unwrap(), vec![]);
+	
+	RgbImage::from_pixel(10, 10, Rgb([0, 0, 0])).save(objects.join("box_1.png")).unwrap();
+	std::fs::write(objects.join("objects.json"), r#"{
+  "object_images": {
+    "box_1.png": { "ground_width": 0.0, "object_type": 3 },
+    "car_1.png": { "ground_width": 1.0, "object_type": 7 },
+    "ghost.png": { "ground_width": 1.0, "object_type": 7 }
+  },
+  "object_types": {
+    "7": { "name": "car" }
+  }
+}"#).unwrap();
+	
+	assert_eq!(lint(&objects).unwrap(), vec![
+		LintIssue::NoAlphaChannel { sprite: "box_1.png".to_string() },
+		LintIssue::UndefinedObjectType { sprite: "box_1.png".to_string(), object_type: 3 },
+		LintIssue::NonPositiveGroundWidth { sprite: "box_1.png".to_string(), ground_width: 0.0 },
+		LintIssue::MissingFile { sprite: "ghost.png".to_string() },
+		LintIssue::MissingDetails { sprite: "tire_1.png".to_string() },
+	]);
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}
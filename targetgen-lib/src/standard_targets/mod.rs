@@ -0,0 +1,368 @@
+use std::f32::consts::PI;
+use ab_glyph::{FontRef, PxScale};
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgba, RgbaImage};
+use imageproc::point::Point;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use crate::generator::coco::CocoCategory;
+use crate::generator::placement::Attributes;
+use crate::objects::{trim_transparent, Object, PROCEDURAL_OBJECT_ID};
+
+/// DejaVu Sans Bold, see assets/fonts/LICENSE-DejaVu.txt
+pub(crate) static FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans-Bold.ttf");
+
+/// Standard targets are drawn this many times larger than their size in the image, then scaled down
+/// to smooth their edges
+const SUPERSAMPLING: u32 = 4;
+
+/// The alphanumeric characters a standard target can carry
+pub const CHARACTERS: [char; 36] = [
+	'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S',
+	'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '0', '1', '2', '3', '4', '5', '6', '7', '8', '9',
+];
+
+/// The shapes of the SUAS standard targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Shape {
+	Circle,
+	Semicircle,
+	QuarterCircle,
+	Triangle,
+	Rectangle,
+	Pentagon,
+	Star,
+	Cross,
+}
+
+impl Shape {
+	pub const ALL: [Shape; 8] = [Shape::Circle, Shape::Semicircle, Shape::QuarterCircle, Shape::Triangle, Shape::Rectangle, Shape::Pentagon, Shape::Star, Shape::Cross];
+	
+	pub fn name(&self) -> &'static str {
+		match self {
+			Shape::Circle => "circle",
+			Shape::Semicircle => "semicircle",
+			Shape::QuarterCircle => "quarter_circle",
+			Shape::Triangle => "triangle",
+			Shape::Rectangle => "rectangle",
+			Shape::Pentagon => "pentagon",
+			Shape::Star => "star",
+			Shape::Cross => "cross",
+		}
+	}
+	
+	/// The outline of the shape inside of a [size] by [size] square
+	fn outline(&self, size: f32) -> Vec<(f32, f32)> {
+		let (center, radius, margin) = (size / 2.0, size * 0.48, size * 0.02);
+		let arc = |cx: f32, cy: f32, r: f32, from: f32, to: f32| (0..=32).map(move |i| {
+			let angle = from + (to - from) * i as f32 / 32.0;
+			(cx + r * angle.cos(), cy - r * angle.sin())
+		});
+		let polygon = |points: usize, inner: Option<f32>| {
+			let corners = if inner.is_some() { points * 2 } else { points };
+			(0..corners).map(|i| {
+				let r = match inner {
+					Some(inner) if i % 2 == 1 => inner,
+					_ => radius,
+				};
+				let angle = -PI / 2.0 + 2.0 * PI * i as f32 / corners as f32;
+				(center + r * angle.cos(), center + r * angle.sin())
+			}).collect::<Vec<(f32, f32)>>()
+		};
+		
+		match self {
+			Shape::Circle => arc(center, center, radius, 0.0, 2.0 * PI).skip(1).collect(),
+			Shape::Semicircle => arc(center, center + radius / 2.0, radius, 0.0, PI).collect(),
+			Shape::QuarterCircle => {
+				let r = size - 2.0 * margin;
+				let mut points = arc(margin, size - margin, r, 0.0, PI / 2.0).collect::<Vec<(f32, f32)>>();
+				points.push((margin, size - margin));
+				points
+			}
+			Shape::Triangle => vec![(center, margin), (size - margin, size - margin), (margin, size - margin)],
+			Shape::Rectangle => vec![(margin, size * 0.2), (size - margin, size * 0.2), (size - margin, size * 0.8), (margin, size * 0.8)],
+			Shape::Pentagon => polygon(5, None),
+			Shape::Star => polygon(5, Some(radius * 0.45)),
+			Shape::Cross => {
+				let (a, b) = (size / 3.0, size * 2.0 / 3.0);
+				let (lo, hi) = (margin, size - margin);
+				vec![(a, lo), (b, lo), (b, a), (hi, a), (hi, b), (b, b), (b, hi), (a, hi), (a, b), (lo, b), (lo, a), (a, a)]
+			}
+		}
+	}
+	
+	/// Where the character is centered and how tall it is, as fractions of the target size, so that it
+	/// fits inside of the shape
+	fn character_placement(&self) -> ((f32, f32), f32) {
+		match self {
+			Shape::Semicircle => ((0.5, 0.6), 0.3),
+			Shape::QuarterCircle => ((0.38, 0.62), 0.4),
+			Shape::Triangle => ((0.5, 0.65), 0.35),
+			Shape::Star => ((0.5, 0.53), 0.25),
+			Shape::Cross => ((0.5, 0.5), 0.3),
+			_ => ((0.5, 0.5), 0.45),
+		}
+	}
+}
+
+/// The colors of the SUAS standard targets and their characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetColor {
+	White,
+	Black,
+	Red,
+	Blue,
+	Green,
+	Purple,
+	Brown,
+	Orange,
+}
+
+impl TargetColor {
+	pub const ALL: [TargetColor; 8] = [TargetColor::White, TargetColor::Black, TargetColor::Red, TargetColor::Blue, TargetColor::Green, TargetColor::Purple, TargetColor::Brown, TargetColor::Orange];
+	
+	pub fn name(&self) -> &'static str {
+		match self {
+			TargetColor::White => "white",
+			TargetColor::Black => "black",
+			TargetColor::Red => "red",
+			TargetColor::Blue => "blue",
+			TargetColor::Green => "green",
+			TargetColor::Purple => "purple",
+			TargetColor::Brown => "brown",
+			TargetColor::Orange => "orange",
+		}
+	}
+	
+	pub fn rgba(&self) -> Rgba<u8> {
+		match self {
+			TargetColor::White => Rgba([245, 245, 245, 255]),
+			TargetColor::Black => Rgba([20, 20, 20, 255]),
+			TargetColor::Red => Rgba([200, 30, 30, 255]),
+			TargetColor::Blue => Rgba([30, 60, 190, 255]),
+			TargetColor::Green => Rgba([40, 150, 50, 255]),
+			TargetColor::Purple => Rgba([120, 40, 150, 255]),
+			TargetColor::Brown => Rgba([120, 75, 35, 255]),
+			TargetColor::Orange => Rgba([240, 130, 20, 255]),
+		}
+	}
+}
+
+/// How many standard targets are placed in each image and how large they are
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StandardTargetConfig {
+	/// the fewest standard targets placed in an image, on top of the regular objects
+	pub min_count: u32,
+	/// the most standard targets placed in an image
+	pub max_count: u32,
+	/// the smallest width of a standard target in meters
+	pub min_size: f32,
+	/// the largest width of a standard target in meters
+	pub max_size: f32,
+	/// the category all standard targets are annotated with, their shape, colors and character are attributes.
+	/// Must not be the id of an object class.
+	pub category_id: u32,
+}
+
+impl Default for StandardTargetConfig {
+	fn default() -> Self {
+		Self {
+			min_count: 0,
+			max_count: 2,
+			min_size: 0.3,
+			max_size: 1.2,
+			category_id: 100,
+		}
+	}
+}
+
+impl StandardTargetConfig {
+	pub fn category(&self) -> CocoCategory {
+		CocoCategory::new(self.category_id, "standard_target".to_string())
+	}
+}
+
+/// A single standard target
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StandardTarget {
+	pub shape: Shape,
+	pub shape_color: TargetColor,
+	pub character: char,
+	pub character_color: TargetColor,
+	/// the width of the target in meters
+	pub size: f32,
+}
+
+impl StandardTarget {
+	/// Pick a random target, the character color always differs from the shape color
+	pub fn random<R: Rng + ?Sized>(config: &StandardTargetConfig, rng: &mut R) -> Self {
+		let shape = *Shape::ALL.choose(rng).unwrap();
+		let shape_color = *TargetColor::ALL.choose(rng).unwrap();
+		let character = *CHARACTERS.choose(rng).unwrap();
+		let character_colors = TargetColor::ALL.iter().filter(|c| **c != shape_color).collect::<Vec<&TargetColor>>();
+		let character_color = **character_colors.choose(rng).unwrap();
+		let size = rng.gen_range(config.min_size..=config.max_size.max(config.min_size));
+		
+		Self { shape, shape_color, character, character_color, size }
+	}
+	
+	/// The annotation attributes of the target
+	pub fn attributes(&self) -> Attributes {
+		Attributes::from([
+			("shape".to_string(), self.shape.name().into()),
+			("shape_color".to_string(), self.shape_color.name().into()),
+			("character".to_string(), self.character.to_string().into()),
+			("character_color".to_string(), self.character_color.name().into()),
+		])
+	}
+	
+	/// A readable name for the target, ex. red_star_A_white
+	pub fn name(&self) -> String {
+		format!("{}_{}_{}_{}", self.shape_color.name(), self.shape.name(), self.character, self.character_color.name())
+	}
+}
+
+/// Draws SUAS standard targets, colored shapes that carry a contrasting alphanumeric character. They are
+/// placed like the objects of the [crate::objects::ObjectManager], but drawn for each image instead of
+/// loaded from sprites.
+pub struct StandardTargetSource {
+	font: FontRef<'static>,
+}
+
+impl Default for StandardTargetSource {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl StandardTargetSource {
+	pub fn new() -> Self {
+		Self {
+			font: FontRef::try_from_slice(FONT).expect("The bundled font is valid"),
+		}
+	}
+	
+	/// Draw a target onto a transparent square that is [pixels] wide
+	pub fn render(&self, target: &StandardTarget, pixels: u32) -> RgbaImage {
+		let pixels = pixels.max(1);
+		let size = (pixels * SUPERSAMPLING) as f32;
+		let mut image = RgbaImage::new(pixels * SUPERSAMPLING, pixels * SUPERSAMPLING);
+		
+		let mut outline = target.shape.outline(size).into_iter().map(|(x, y)| Point::new(x.round() as i32, y.round() as i32)).collect::<Vec<Point<i32>>>();
+		outline.dedup();
+		imageproc::drawing::draw_polygon_mut(&mut image, &outline, target.shape_color.rgba());
+		
+		let ((x, y), height) = target.shape.character_placement();
+		let scale = PxScale::from(size * height / 0.73); // the cap height of the font is 0.73 of its size
+		let text = target.character.to_string();
+		let (w, h) = imageproc::drawing::text_size(scale, &self.font, &text);
+		imageproc::drawing::draw_text_mut(&mut image, target.character_color.rgba(), (size * x - w as f32 / 2.0) as i32, (size * y - h as f32 / 2.0) as i32, scale, &self.font, &text);
+		
+		image::imageops::resize(&image, pixels, pixels, FilterType::Triangle)
+	}
+	
+	/// Pick and draw the standard targets of one image at its ground resolution, as objects that can be
+	/// placed like sprites
+	pub fn generate_set<R: Rng + ?Sized>(&self, config: &StandardTargetConfig, pixels_per_meter: f32, rng: &mut R) -> Vec<Object> {
+		let count = rng.gen_range(config.min_count..=config.max_count.max(config.min_count));
+		
+		(0..count).map(|_| {
+			let target = StandardTarget::random(config, rng);
+			let pixels = (target.size * pixels_per_meter).round().max(1.0) as u32;
+			let (dynamic_image, padding) = trim_transparent(DynamicImage::ImageRgba8(self.render(&target, pixels)));
+			
+			Object {
+				object_class: config.category_id,
+				id: PROCEDURAL_OBJECT_ID,
+				file_name: target.name(),
+				// the target fits in a square of its size, keep the trimmed shape at the same scale
+				object_width_meters: target.size * dynamic_image.width() as f32 / pixels as f32,
+				dynamic_image,
+				attributes: target.attributes(),
+				recolor: None,
+				views: vec![],
+				padding,
+			}
+		}).collect()
+	}
+}
+
+#[test]
+fn test_render_standard_targets() {
+	let source = StandardTargetSource::new();
+	
+	for shape in Shape::ALL {
+		let target = StandardTarget {
+			shape,
+			shape_color: TargetColor::Red,
+			character: 'A',
+			character_color: TargetColor::White,
+			size: 0.5,
+		};
+		let image = source.render(&target, 64);
+		assert_eq!(image.dimensions(), (64, 64));
+		
+		let count = |color: Rgba<u8>| image.pixels().filter(|p| (0..4).all(|c| p[c].abs_diff(color[c]) < 40)).count();
+		let (shape_pixels, character_pixels) = (count(TargetColor::Red.rgba()), count(TargetColor::White.rgba()));
+		let transparent = image.pixels().filter(|p| p[3] == 0).count();
+		
+		assert!(shape_pixels > 300, "{} has {} shape pixels", shape.name(), shape_pixels);
+		assert!(character_pixels > 20, "{} has {} character pixels", shape.name(), character_pixels);
+		assert!(transparent > 0, "{} fills the whole image", shape.name());
+	}
+	
+	let config = StandardTargetConfig { min_count: 3, max_count: 3, ..Default::default() };
+	let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(3);
+	let set = source.generate_set(&config, 45.0, &mut rng);
+	assert_eq!(set.len(), 3);
+	
+	for object in set {
+		assert_eq!(object.object_class, 100);
+		assert_ne!(object.attributes["shape_color"], object.attributes["character_color"]);
+		assert!((0.3..=1.2).contains(&object.object_width_meters));
+		assert_eq!(object.dynamic_image.width(), (object.object_width_meters * 45.0).round() as u32);
+		assert!(object.dynamic_image.width().max(object.dynamic_image.height()) <= (1.2f32 * 45.0).round() as u32);
+	}
+}
+
+#[test]
+fn test_generate_with_standard_targets() {
+	use crate::generator::coco::CocoFormatFile;
+	use crate::generator::TargetGenerator;
+	use crate::generator::util;
+	
+	let dir = util::test_dir("standard_targets");
+	let (backgrounds, objects) = util::create_test_assets(&dir);
+	let output = dir.join("output");
+	std::fs::create_dir_all(&output).unwrap();
+	
+	let mut tg = TargetGenerator::new(backgrounds, objects, output.join("annotations.json")).unwrap();
+	tg.config.permit_duplicates = true;
+	
+	// the category of the standard targets cannot be one of the object classes
+	tg.config.standard_targets = Some(StandardTargetConfig { category_id: 7, ..Default::default() });
+	assert!(tg.generate_targets(6, ..2, &output).is_err());
+	
+	tg.config.standard_targets = Some(StandardTargetConfig { min_count: 1, max_count: 2, ..Default::default() });
+	tg.generate_targets(6, ..2, &output).unwrap();
+	tg.close();
+	
+	let file = CocoFormatFile::load(output.join("annotations.json")).unwrap();
+	assert!(file.categories().iter().any(|c| c.id() == 100 && c.name() == "standard_target"));
+	
+	let targets = file.annotations().iter().filter(|a| a.category_id() == 100).collect::<Vec<_>>();
+	assert!(!targets.is_empty());
+	
+	for target in targets {
+		for attribute in ["shape", "shape_color", "character", "character_color"] {
+			assert!(target.attributes().contains_key(attribute), "missing {}", attribute);
+		}
+	}
+	
+	assert!(file.annotations().iter().filter(|a| a.category_id() != 100).all(|a| a.attributes().is_empty()));
+	
+	std::fs::remove_dir_all(dir).unwrap();
+}